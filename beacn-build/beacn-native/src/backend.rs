@@ -0,0 +1,234 @@
+use napi_derive::napi;
+
+use crate::pactl::run_command;
+
+/// Sound server found by `initialize`; reaches JS as `"pulseaudio"`, `"pipewire"` or
+/// `"unknown"`.
+#[napi(string_enum = "lowercase")]
+#[derive(Debug, PartialEq)]
+pub enum Backend {
+    PulseAudio,
+    PipeWire,
+    Unknown,
+}
+
+impl Backend {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Backend::PulseAudio => "pulseaudio",
+            Backend::PipeWire => "pipewire",
+            Backend::Unknown => "unknown",
+        }
+    }
+}
+
+/// Works out which sound server is running. pipewire-pulse speaks the PulseAudio
+/// protocol but fails `pulseaudio --check`, so fall back to asking PipeWire directly.
+pub fn detect_backend() -> Backend {
+    if command_succeeds("pulseaudio", &["--check"]) {
+        Backend::PulseAudio
+    } else if command_succeeds("pw-cli", &["info", "0"]) || command_succeeds("wpctl", &["status"]) {
+        Backend::PipeWire
+    } else {
+        Backend::Unknown
+    }
+}
+
+/// Classifies a server from the `Server Name` line of `pactl info`, e.g.
+/// `PulseAudio (on PipeWire 1.0.5)` or `pulseaudio`.
+pub fn backend_from_server_name(name: &str) -> Backend {
+    if name.contains("PipeWire") {
+        Backend::PipeWire
+    } else if name.to_lowercase().contains("pulseaudio") {
+        Backend::PulseAudio
+    } else {
+        Backend::Unknown
+    }
+}
+
+pub fn command_succeeds(program: &str, args: &[&str]) -> bool {
+    run_command(program, args).is_ok()
+}
+
+/// One port-level connection in the PipeWire graph.
+#[napi(object)]
+#[derive(Debug, PartialEq)]
+pub struct PwLink {
+    /// `node:port` the audio leaves, e.g. `BEACN_Link_Out:monitor_FL`
+    pub output_port: String,
+    pub input_port: String,
+}
+
+/// Links from `pw-link -l`. Every link is listed under both of its ports; only the
+/// `|->` side is kept so each appears once.
+pub fn parse_pw_links(output: &str) -> Vec<PwLink> {
+    let mut links = Vec::new();
+    let mut port = None;
+
+    for line in output.lines() {
+        let entry = line.trim();
+        if !line.starts_with(char::is_whitespace) {
+            port = Some(entry);
+        } else if let (Some(output_port), Some(input_port)) = (port, entry.strip_prefix("|->")) {
+            links.push(PwLink { output_port: output_port.to_string(), input_port: input_port.trim().to_string() });
+        }
+    }
+
+    links
+}
+
+/// The ports of `node` in a `pw-link -o` or `pw-link -i` listing, in listed order.
+pub fn parse_pw_ports(output: &str, node: &str) -> Vec<String> {
+    let prefix = format!("{}:", node);
+    output.lines().map(str::trim).filter(|port| port.starts_with(&prefix)).map(str::to_string).collect()
+}
+
+/// Pairs output ports with input ports by channel (the part after the last `_`, as
+/// in `monitor_FL` and `playback_FL`). A mono end is linked to every port of the
+/// other; layouts with no channel in common are paired in order.
+pub fn pair_pw_ports(outputs: &[String], inputs: &[String]) -> Vec<(String, String)> {
+    let channel = |port: &String| port.rsplit_once('_').map_or(port.clone(), |(_, c)| c.to_string());
+
+    match (outputs.len(), inputs.len()) {
+        (0, _) | (_, 0) => Vec::new(),
+        (1, _) => inputs.iter().map(|i| (outputs[0].clone(), i.clone())).collect(),
+        (_, 1) => outputs.iter().map(|o| (o.clone(), inputs[0].clone())).collect(),
+        _ => {
+            let by_channel: Vec<(String, String)> = outputs
+                .iter()
+                .filter_map(|o| inputs.iter().find(|i| channel(i) == channel(o)).map(|i| (o.clone(), i.clone())))
+                .collect();
+            if by_channel.is_empty() {
+                outputs.iter().cloned().zip(inputs.iter().cloned()).collect()
+            } else {
+                by_channel
+            }
+        }
+    }
+}
+
+/// `(id, label)` for each node under `section` (`"Sinks"` or `"Sources"`) of the
+/// Audio part of `wpctl status`. The label is what wpctl shows, usually the node's
+/// description, with the default marker and `[vol: ...]` suffix removed.
+pub fn parse_wpctl_nodes(status: &str, section: &str) -> Vec<(u32, String)> {
+    let header = format!("{}:", section);
+    let mut in_audio = false;
+    let mut in_section = false;
+    let mut nodes = Vec::new();
+
+    for line in status.lines() {
+        // Top level headings (`Audio`, `Video`, `Settings`) start in the first column
+        if line.chars().next().is_some_and(|c| c.is_alphabetic()) {
+            in_audio = line.trim() == "Audio";
+            in_section = false;
+            continue;
+        }
+
+        let entry = line.trim_start_matches(|c: char| c.is_whitespace() || matches!(c, '│' | '├' | '└' | '─'));
+        if line.contains('├') || line.contains('└') {
+            in_section = in_audio && entry.trim() == header;
+            continue;
+        }
+        if !in_section {
+            continue;
+        }
+
+        let entry = entry.trim_start_matches(|c: char| c.is_whitespace() || c == '*');
+        let Some((id, rest)) = entry.split_once(". ") else { continue };
+        let Ok(id) = id.trim().parse() else { continue };
+        let label = match rest.rfind(" [") {
+            Some(end) => &rest[..end],
+            None => rest,
+        };
+        nodes.push((id, label.trim().to_string()));
+    }
+
+    nodes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn wpctl_nodes_come_from_the_audio_section_only() {
+        let status = "\
+PipeWire 'pipewire-0' [1.0.5, user@host, cookie:1234]
+ └─ Clients:
+        33. WirePlumber                         [1.0.5, user@host, pid:1000]
+
+Audio
+ ├─ Devices:
+ │      42. Built-in Audio                      [alsa]
+ │
+ ├─ Sinks:
+ │  *   50. Built-in Audio Analog Stereo        [vol: 0.40]
+ │      61. BEACN Link Out                      [vol: 1.00 MUTED]
+ │
+ ├─ Sources:
+ │  *   51. Built-in Audio Analog Stereo        [vol: 1.00]
+ │
+ └─ Streams:
+
+Video
+ ├─ Sinks:
+ │      70. Camera sink
+ │
+";
+
+        assert_eq!(
+            parse_wpctl_nodes(status, "Sinks"),
+            vec![(50, "Built-in Audio Analog Stereo".to_string()), (61, "BEACN Link Out".to_string())]
+        );
+        assert_eq!(parse_wpctl_nodes(status, "Sources"), vec![(51, "Built-in Audio Analog Stereo".to_string())]);
+    }
+
+    #[test]
+    fn pw_ports_pair_up_by_channel() {
+        let outputs = "BEACN_Link_Out:monitor_FL\nBEACN_Link_Out:monitor_FR\nBEACN_Link_Out_2:monitor_FL\n";
+        let inputs = "alsa_output.usb-BEACN_Mix:playback_FR\nalsa_output.usb-BEACN_Mix:playback_FL\n";
+        let monitor = parse_pw_ports(outputs, "BEACN_Link_Out");
+        let hardware = parse_pw_ports(inputs, "alsa_output.usb-BEACN_Mix");
+
+        assert_eq!(monitor, ["BEACN_Link_Out:monitor_FL", "BEACN_Link_Out:monitor_FR"]);
+        assert_eq!(
+            pair_pw_ports(&monitor, &hardware),
+            [
+                ("BEACN_Link_Out:monitor_FL".to_string(), "alsa_output.usb-BEACN_Mix:playback_FL".to_string()),
+                ("BEACN_Link_Out:monitor_FR".to_string(), "alsa_output.usb-BEACN_Mix:playback_FR".to_string()),
+            ]
+        );
+
+        let mic = vec!["mic:capture_MONO".to_string()];
+        assert_eq!(pair_pw_ports(&mic, &hardware).len(), 2);
+        assert_eq!(
+            pair_pw_ports(&monitor, &mic)[1],
+            ("BEACN_Link_Out:monitor_FR".to_string(), "mic:capture_MONO".to_string())
+        );
+        assert!(pair_pw_ports(&monitor, &[]).is_empty());
+    }
+
+    #[test]
+    fn pw_links_are_listed_once_per_connection() {
+        let output = "\
+BEACN_Link_Out:monitor_FL
+  |-> alsa_output.usb-BEACN_Mix:playback_FL
+BEACN_Link_Out:monitor_FR
+  |-> alsa_output.usb-BEACN_Mix:playback_FR
+alsa_output.usb-BEACN_Mix:playback_FL
+  |<- BEACN_Link_Out:monitor_FL
+alsa_output.usb-BEACN_Mix:playback_FR
+  |<- BEACN_Link_Out:monitor_FR
+";
+
+        let links = parse_pw_links(output);
+        assert_eq!(links.len(), 2);
+        assert_eq!(
+            links[0],
+            PwLink {
+                output_port: "BEACN_Link_Out:monitor_FL".to_string(),
+                input_port: "alsa_output.usb-BEACN_Mix:playback_FL".to_string(),
+            }
+        );
+    }
+}
@@ -1,20 +1,504 @@
+use napi::bindgen_prelude::AsyncTask;
+use napi::threadsafe_function::{
+    ErrorStrategy, ThreadSafeCallContext, ThreadsafeFunction, ThreadsafeFunctionCallMode,
+};
+use napi::{Env, JsFunction, Task};
 use napi_derive::napi;
-use std::sync::{Arc, Mutex};
+use std::cell::Cell;
+use std::collections::HashMap;
+use std::rc::Rc;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::Arc;
 use libpulse_binding as pulse;
-use pulse::context::Context;
-use pulse::mainloop::standard::Mainloop;
+use parking_lot::Mutex;
+use pulse::context::subscribe::{Facility, InterestMaskSet, Operation};
+use pulse::context::{Context, State};
+use pulse::mainloop::standard::{IterateResult, Mainloop};
+
+use crate::backend::{
+    backend_from_server_name, command_succeeds, detect_backend, pair_pw_ports, parse_pw_links,
+    parse_pw_ports, parse_wpctl_nodes, Backend, PwLink,
+};
+use crate::config::{
+    read_config, write_config, FormatConfig, LinkConfig, OutputConfig, RouteConfig, SourceConfig, CONFIG_VERSION,
+};
+use crate::connection::{connect_context, OwnedMainloop, DEFAULT_CONNECT_ITERATIONS};
+use crate::error::BeacnError;
+use crate::hardware::{scan_beacn_devices, BeacnDevice, USB_DEVICES_DIR};
+use crate::logging::{self, LogLevel, LogRecord};
+use crate::metering::{run_meter, MeterLevel};
+use crate::native::{native_devices, native_info, write_stream_device};
+use crate::pactl::{
+    average_percent, command_line, description_arg, find_sink, find_source, list_modules,
+    module_arg, parse_blocks, parse_channel_percents, parse_configured_latency_usec, parse_info,
+    parse_latency_usec, parse_module_args, parse_sample_spec, parse_section_entry, parse_yes_no,
+    properties_arg, pulse_server, run_command, run_pactl, set_command_timeout, set_pulse_server,
+    spawn_command, validate_sink_name, Block,
+};
+use crate::recording::{create_wav, run_recording, RecordingHandle, WavFormat};
+use crate::tone::{run_tone, sine_samples};
+use crate::worker::Worker;
+
+// How often the device watcher services its mainloop and checks for a stop request
+const WATCH_POLL_MS: u64 = 50;
+const AUTO_RESTORE_POLL_MS: u64 = 1000;
+// Fades change the volume in steps this far apart, small enough to avoid zipper noise
+const FADE_STEP_MS: u32 = 20;
+// Matches the upper end of pavucontrol's slider; anything louder is almost certainly a mistake
+const MAX_VOLUME_PERCENT: u32 = 150;
+// Stamped onto both loopback streams so routes made by BEACN Link are recognisable in pavucontrol
+const ROUTE_STREAM_PROPERTIES: &str = "beacn.link.route=1 media.name='BEACN Link Route'";
+const TONE_FREQ_RANGE_HZ: std::ops::RangeInclusive<u32> = 20..=20000;
+const MAX_TONE_DURATION_MS: u32 = 10_000;
+// swh-plugins' 15 band graphic EQ, bands at 50, 100, 156, 220, 311, 440, 622, 880,
+// 1250, 1750, 2500, 3500, 5000, 10000 and 20000 Hz
+const EQ_PLUGIN: &str = "mbeq_1197";
+const EQ_LABEL: &str = "mbeq";
+const EQ_BAND_COUNT: usize = 15;
+const EQ_GAIN_RANGE_DB: std::ops::RangeInclusive<f64> = -70.0..=30.0;
+// Searched when LADSPA_PATH isn't set
+const LADSPA_DIRS: [&str; 4] =
+    ["/usr/lib/ladspa", "/usr/lib64/ladspa", "/usr/local/lib/ladspa", "/usr/lib/x86_64-linux-gnu/ladspa"];
+const MAX_SINK_CHANNELS: u8 = 8;
+const SUPPORTED_SAMPLE_RATES: [u32; 3] = [44100, 48000, 96000];
+const SUPPORTED_SAMPLE_FORMATS: [&str; 8] =
+    ["u8", "s16le", "s16be", "s24le", "s24be", "s32le", "s32be", "float32le"];
+// Position names PulseAudio's channel map parser accepts, besides aux0-aux31
+const CHANNEL_POSITIONS: [&str; 23] = [
+    "mono", "left", "right", "center", "front-left", "front-right", "front-center", "rear-center",
+    "rear-left", "rear-right", "lfe", "subwoofer", "front-left-of-center", "front-right-of-center",
+    "side-left", "side-right", "top-center", "top-front-left", "top-front-right", "top-front-center",
+    "top-rear-left", "top-rear-right", "top-rear-center",
+];
+const VIRTUAL_SOURCE_SINK_SUFFIX: &str = "_Sink";
+const DEFAULT_LINK_PREFIX: &str = "BEACN_Link";
+// BEACN's own software exposes four outputs
+const DEFAULT_LINK_OUTPUT_COUNT: u32 = 4;
+const LINK_OUTPUT_COUNT_RANGE: std::ops::RangeInclusive<u32> = 1..=32;
+// Sink properties that stop PipeWire and PulseAudio's module-suspend-on-idle from
+// suspending a sink. PulseAudio has no per-device "never", so it gets a year.
+const NO_IDLE_SUSPEND_PROPERTIES: [&str; 2] =
+    ["session.suspend-timeout-seconds=0", "module-suspend-on-idle.timeout=31536000"];
+// Null sink `self_test` loads and unloads straight away
+const SELF_TEST_SINK: &str = "BEACN_Link_SelfTest";
+// Two seconds either way covers any capture card's video delay
+const MAX_LATENCY_OFFSET_USEC: i64 = 2_000_000;
+const LOOPBACK_LATENCY_RANGE_MS: std::ops::RangeInclusive<u32> = 1..=2000;
+// Long enough for a loopback's buffers to fill at the default latency
+const DEFAULT_ROUTE_SETTLE_MS: u32 = 150;
+const MAX_ROUTE_SETTLE_MS: u32 = 5000;
+
+#[napi(string_enum)]
+#[derive(Debug, PartialEq)]
+pub enum DeviceKind {
+    Sink,
+    Source,
+    /// The source that carries what a sink plays; not a real input
+    MonitorSource,
+}
 
 #[napi]
 pub struct AudioDevice {
     pub name: String,
+    /// `index` as a string, as earlier versions reported it
     pub id: String,
+    pub index: u32,
+    pub description: String,
+    pub kind: DeviceKind,
+    pub is_monitor: bool,
+    /// For monitor sources, the sink they carry
+    pub monitor_of: Option<String>,
+    pub sample_rate: u32,
+    pub channels: u8,
+}
+
+#[napi]
+impl AudioDevice {
+    /// True for sinks. Kept for callers written before `kind` existed.
+    #[napi(getter)]
+    pub fn is_output(&self) -> bool {
+        self.kind == DeviceKind::Sink
+    }
+}
+
+#[napi(object)]
+pub struct AudioDeviceDetail {
+    pub index: u32,
+    pub name: String,
     pub description: String,
+    pub kind: DeviceKind,
     pub is_output: bool,
+    pub channels: u32,
+    pub sample_rate: u32,
+    pub sample_format: String,
+    /// RUNNING, IDLE or SUSPENDED
+    pub state: String,
+    pub volume_percent: u32,
+    pub muted: bool,
+    /// For sinks, the source carrying what the sink plays
+    pub monitor_source: Option<String>,
+}
+
+#[napi(object)]
+pub struct SinkInput {
+    pub id: u32,
+    pub app_name: Option<String>,
+    pub media_name: Option<String>,
+    /// Name of the sink the stream is currently playing to
+    pub current_sink: Option<String>,
+    pub volume_percent: u32,
+    pub muted: bool,
+}
+
+#[napi(object)]
+pub struct SourceOutput {
+    pub id: u32,
+    pub app_name: Option<String>,
+    pub media_name: Option<String>,
+    /// Name of the source the stream is currently recording from
+    pub current_source: Option<String>,
+    pub volume_percent: u32,
+    pub muted: bool,
+}
+
+/// Sink format for `create_virtual_output`. Anything left out is the server's default.
+#[napi(object)]
+pub struct SinkFormat {
+    pub channels: Option<u8>,
+    pub rate: Option<u32>,
+    /// PulseAudio sample format name, e.g. `s16le` or `float32le`
+    pub format: Option<String>,
+    /// Comma separated channel positions, e.g. `front-left,front-right`; without
+    /// `channels` the count is taken from the map
+    pub channel_map: Option<String>,
+}
+
+impl SinkFormat {
+    fn module_args(&self) -> Result<Vec<String>, BeacnError> {
+        let mut args = Vec::new();
+
+        if let Some(channels) = self.channels {
+            if !(1..=MAX_SINK_CHANNELS).contains(&channels) {
+                return Err(BeacnError::InvalidArgument(format!(
+                    "{} channels is outside 1-{}",
+                    channels, MAX_SINK_CHANNELS
+                )));
+            }
+            args.push(format!("channels={}", channels));
+        }
+
+        if let Some(rate) = self.rate {
+            if !SUPPORTED_SAMPLE_RATES.contains(&rate) {
+                return Err(BeacnError::InvalidArgument(format!(
+                    "sample rate {} is not one of {:?}",
+                    rate, SUPPORTED_SAMPLE_RATES
+                )));
+            }
+            args.push(format!("rate={}", rate));
+        }
+
+        if let Some(format) = &self.format {
+            if !SUPPORTED_SAMPLE_FORMATS.contains(&format.as_str()) {
+                return Err(BeacnError::InvalidArgument(format!(
+                    "sample format '{}' is not one of {:?}",
+                    format, SUPPORTED_SAMPLE_FORMATS
+                )));
+            }
+            args.push(format!("format={}", format));
+        }
+
+        if let Some(map) = &self.channel_map {
+            let positions = map.split(',').filter(|p| !p.trim().is_empty()).count();
+            let channels = self.channels.unwrap_or(positions.min(MAX_SINK_CHANNELS as usize) as u8);
+            args.push(format!("channel_map={}", channel_map_arg(map, channels)?));
+        }
+
+        Ok(args)
+    }
+}
+
+impl From<FormatConfig> for SinkFormat {
+    fn from(saved: FormatConfig) -> Self {
+        SinkFormat { channels: saved.channels, rate: saved.rate, format: saved.format, channel_map: saved.channel_map }
+    }
+}
+
+#[napi(string_enum)]
+pub enum SurroundLayout {
+    Stereo,
+    Surround51,
+    Surround71,
+}
+
+impl SurroundLayout {
+    /// Channel count and PulseAudio's standard map for the layout.
+    fn channel_map(&self) -> (u8, &'static str) {
+        match self {
+            SurroundLayout::Stereo => (2, "front-left,front-right"),
+            SurroundLayout::Surround51 => (6, "front-left,front-right,rear-left,rear-right,front-center,lfe"),
+            SurroundLayout::Surround71 => {
+                (8, "front-left,front-right,rear-left,rear-right,front-center,lfe,side-left,side-right")
+            }
+        }
+    }
+}
+
+#[napi(object)]
+pub struct SinkVolume {
+    pub volume_percent: u32,
+    pub muted: bool,
+    pub channels: u32,
+}
+
+#[napi(object)]
+pub struct RouteInfo {
+    /// `None` for a route `route_audio` made with `pw-link`
+    pub module_id: Option<u32>,
+    pub source: Option<String>,
+    pub sink: Option<String>,
+    pub latency_msec: Option<u32>,
+    /// The source and sink run at different sample rates, so the loopback resamples
+    pub resampling: bool,
+    /// Both endpoints still exist; false once either has been unplugged or removed
+    pub valid: bool,
+    /// Whether the loopback up- or downmixes between channel layouts (`remix=`);
+    /// `pw-link` routes link ports by channel, so never
+    pub remix: bool,
+}
+
+#[napi(object)]
+pub struct RoutingTable {
+    pub nodes: Vec<Node>,
+    pub edges: Vec<Edge>,
+}
+
+#[napi(object)]
+pub struct Node {
+    pub index: u32,
+    pub name: String,
+    pub kind: DeviceKind,
+}
+
+/// One route, from a source to a sink.
+#[napi(object)]
+pub struct Edge {
+    /// `None` for a `pw-link` route
+    pub module_id: Option<u32>,
+    pub from: String,
+    pub to: String,
+    pub latency_msec: Option<u32>,
+    /// The route's own gain, as set with `set_route_volume`
+    pub volume_percent: u32,
+}
+
+#[napi(object)]
+pub struct ServerInfo {
+    /// e.g. `pulseaudio` or `PulseAudio (on PipeWire 1.0.5)`
+    pub server_name: String,
+    pub server_version: String,
+    /// e.g. `s16le 2ch 44100Hz`
+    pub default_sample_spec: String,
+    pub default_channel_map: String,
+    pub default_sink: Option<String>,
+    pub default_source: Option<String>,
+}
+
+#[napi(object)]
+pub struct CardProfile {
+    pub name: String,
+    pub description: String,
+    pub available: bool,
+}
+
+#[napi(object)]
+pub struct CardInfo {
+    pub index: u32,
+    pub name: String,
+    pub active_profile: Option<String>,
+    pub profiles: Vec<CardProfile>,
+}
+
+#[napi(object)]
+pub struct PortInfo {
+    pub name: String,
+    pub description: String,
+    /// `available`, `unavailable` or `unknown`, e.g. whether headphones are plugged in
+    pub availability: String,
+    pub priority: u32,
+    pub active: bool,
+}
+
+#[napi(object)]
+pub struct PlaybackHandle {
+    pub id: u32,
+    pub sink: String,
+    pub path: String,
+}
+
+#[napi(object)]
+pub struct EqHandle {
+    pub module_id: u32,
+    /// The EQ sink; play (or route) into this instead of the master
+    pub sink_name: String,
+    pub master_sink: String,
+}
+
+/// Outcome of `load_config`. Each entry names the item, e.g. `output BEACN_Link_Out`
+/// or `route a -> b`; skipped and failed entries also say why.
+#[napi(object)]
+pub struct ConfigReport {
+    pub created: Vec<String>,
+    pub skipped: Vec<String>,
+    pub failed: Vec<String>,
+}
+
+#[napi(object)]
+pub struct Capabilities {
+    pub backend: Backend,
+    /// `route_audio` makes plain routes with `pw-link`; `link_nodes` and
+    /// `get_pipewire_links` work
+    pub pw_link: bool,
+    /// `attach_equalizer` can find its LADSPA plugin
+    pub equalizer: bool,
+    /// Default devices are set through WirePlumber with `wpctl`
+    pub wpctl_defaults: bool,
+}
+
+/// Buffer state of a sink and the streams playing into it, from `get_stream_stats`.
+/// Latencies are what the server reports at the moment of the call, so they are
+/// best-effort snapshots. Neither PulseAudio nor pipewire-pulse exposes underrun
+/// counters to clients; a `buffer_latency_usec` that keeps dropping towards zero
+/// is the closest sign that a route is about to crackle.
+#[napi(object)]
+pub struct StreamStats {
+    pub sink: String,
+    /// e.g. `RUNNING`, `IDLE` or `SUSPENDED`
+    pub state: String,
+    pub latency_usec: i64,
+    /// The latency the sink was asked for; `None` if the server doesn't say
+    pub configured_latency_usec: Option<i64>,
+    pub streams: Vec<StreamBufferStats>,
+}
+
+#[napi(object)]
+pub struct StreamBufferStats {
+    pub input_id: u32,
+    pub app_name: Option<String>,
+    /// Set for loopback routes; the module ID `list_routes` reports
+    pub route_module_id: Option<u32>,
+    /// Audio queued in the stream's own buffer
+    pub buffer_latency_usec: Option<i64>,
+    /// Latency the sink adds on top
+    pub sink_latency_usec: Option<i64>,
+}
+
+/// Extra `route_audio` settings; anything left out keeps the loopback default.
+#[napi(object)]
+#[derive(Default)]
+pub struct RouteOptions {
+    /// Pin the loopback to its source so it can't be moved in pavucontrol or by
+    /// automatic routing
+    pub source_dont_move: Option<bool>,
+    /// Pin the loopback to its sink the same way
+    pub sink_dont_move: Option<bool>,
+    /// `false` passes `remix=no`, so channels map by position instead of being up- or
+    /// downmixed between differing layouts. Defaults to `true`.
+    pub remix: Option<bool>,
+    /// Mute the loopback's stream right after it's created and unmute it after
+    /// `settle_ms`, once its buffers have filled, so connecting doesn't pop
+    pub start_muted: Option<bool>,
+    /// How long a `start_muted` route stays muted; 150ms by default
+    pub settle_ms: Option<u32>,
+}
+
+impl RouteOptions {
+    /// Whether anything asked for needs a `module-loopback` rather than a `pw-link`.
+    fn needs_loopback(&self) -> bool {
+        self.source_dont_move == Some(true)
+            || self.sink_dont_move == Some(true)
+            || self.remix == Some(false)
+            || self.start_muted == Some(true)
+    }
+}
+
+/// Outcome of `auto_setup`. Failed entries name the step and the error, so the
+/// setup can simply be run again once the cause is fixed.
+#[napi(object)]
+pub struct SetupReport {
+    pub created_sinks: Vec<String>,
+    /// `monitor -> hardware sink` for each route made
+    pub created_routes: Vec<String>,
+    pub hardware_sink: Option<String>,
+    pub failed: Vec<String>,
+}
+
+#[napi(object)]
+pub struct SelfTestCheck {
+    /// `pactl`, `server`, `load_module` or `mainloop`
+    pub name: String,
+    pub passed: bool,
+    /// What was found, or why the check failed
+    pub message: String,
+}
+
+/// Outcome of `self_test`; `passed` is true only if every check passed.
+#[napi(object)]
+pub struct SelfTestReport {
+    pub passed: bool,
+    pub checks: Vec<SelfTestCheck>,
+}
+
+#[napi(object)]
+pub struct CreatedOutput {
+    /// The name the sink was created under, e.g. `BEACN_Link_Out_2`
+    pub name: String,
+    pub module_id: u32,
+}
+
+/// Outcome of `combine_sink_add_member` / `combine_sink_remove_member`.
+#[napi(object)]
+pub struct CombinedSinkUpdate {
+    /// The combined sink's module ID, new if it was reloaded
+    pub module_id: u32,
+    /// `true` if the sink was unloaded and loaded again rather than updated in place.
+    /// No module-combine-sink takes new `slaves` while loaded, so currently always set.
+    pub reloaded: bool,
+}
+
+/// Outcome of `mute_all` and `set_volume_all`. Failed entries are `sink (error)`.
+#[napi(object)]
+pub struct SinkBatchReport {
+    pub succeeded: Vec<String>,
+    pub failed: Vec<String>,
 }
 
 #[napi]
 pub struct BeacnLink {
+    // Declared before the mainloop so the context is dropped first
     pulse_context: Arc<Mutex<Option<Context>>>,
+    // Shared with `initialize_async`, which connects on the libuv threadpool
+    pulse_mainloop: Arc<Mutex<Option<OwnedMainloop>>>,
+    // Module IDs loaded by this instance, in load order
+    own_modules: Arc<Mutex<Vec<TrackedModule>>>,
+    // Routes made with `pw-link` under PipeWire, which have no module to track
+    own_links: Arc<Mutex<Vec<TrackedLink>>>,
+    backend: Arc<Mutex<Backend>>,
+    connect_iterations: u32,
+    cleanup_on_drop: bool,
+    device_watcher: Mutex<Option<Worker>>,
+    auto_restore: Mutex<Option<Worker>>,
+    // Level meter threads keyed by sink name
+    meters: Mutex<HashMap<String, Worker>>,
+    // Running volume fades keyed by sink name
+    fades: Mutex<HashMap<String, Worker>>,
+    // Test tones keyed by sink name
+    tones: Mutex<HashMap<String, Worker>>,
+    // Recording and playback threads keyed by handle ID
+    recordings: Mutex<HashMap<u32, Worker>>,
+    playbacks: Mutex<HashMap<u32, Worker>>,
+    // Pending `start_muted` unmutes keyed by loopback module ID
+    route_unmutes: Mutex<HashMap<u32, Worker>>,
+    next_handle_id: AtomicU32,
 }
 
 #[napi]
@@ -23,138 +507,3137 @@ impl BeacnLink {
     pub fn new() -> Self {
         BeacnLink {
             pulse_context: Arc::new(Mutex::new(None)),
+            pulse_mainloop: Arc::new(Mutex::new(None)),
+            own_modules: Arc::new(Mutex::new(Vec::new())),
+            own_links: Arc::new(Mutex::new(Vec::new())),
+            backend: Arc::new(Mutex::new(Backend::Unknown)),
+            connect_iterations: DEFAULT_CONNECT_ITERATIONS,
+            cleanup_on_drop: true,
+            device_watcher: Mutex::new(None),
+            auto_restore: Mutex::new(None),
+            meters: Mutex::new(HashMap::new()),
+            fades: Mutex::new(HashMap::new()),
+            tones: Mutex::new(HashMap::new()),
+            recordings: Mutex::new(HashMap::new()),
+            playbacks: Mutex::new(HashMap::new()),
+            route_unmutes: Mutex::new(HashMap::new()),
+            next_handle_id: AtomicU32::new(1),
+        }
+    }
+
+    /// How long any spawned `pactl`/`pw-link` call may run before it is killed and
+    /// `CommandTimedOut` is returned. Applies process wide; defaults to 5 seconds.
+    /// Zero would fail every call, so it is rejected.
+    #[napi]
+    pub fn set_command_timeout(&mut self, ms: u32) -> Result<(), BeacnError> {
+        if ms == 0 {
+            return Err(BeacnError::InvalidArgument("command timeout must be at least 1ms".to_string()));
         }
+        set_command_timeout(ms);
+        Ok(())
+    }
+
+    /// Whether tracked modules are unloaded when this object is garbage collected.
+    /// On by default; turn it off to leave the devices in place after the app exits.
+    #[napi]
+    pub fn set_cleanup_on_drop(&mut self, enabled: bool) {
+        self.cleanup_on_drop = enabled;
+    }
+
+    /// How many 10ms polls `initialize` and each background connection wait for the
+    /// server to become ready before failing with `ConnectionFailed`. Defaults to
+    /// 500, about 5 seconds.
+    #[napi]
+    pub fn set_connect_iterations(&mut self, iterations: u32) {
+        self.connect_iterations = iterations;
     }
 
     #[napi]
     pub fn initialize(&mut self) -> bool {
-        // Check if PulseAudio is running
-        if !std::process::Command::new("pulseaudio")
-            .args(["--check"])
-            .status()
-            .map_or(false, |status| status.success()) {
-            eprintln!("PulseAudio is not running");
-            return false;
-        }
-
-        let mainloop = match Mainloop::new() {
-            Some(m) => m,
-            None => return false,
-        };
+        match self.initialize_with_server(None) {
+            Ok(()) => true,
+            Err(e) => {
+                logging::error(format_args!("{}", e));
+                false
+            }
+        }
+    }
 
-        let context = match Context::new(&mainloop, "BEACN Link") {
-            Some(c) => c,
-            None => return false,
-        };
+    /// Connects to `server` (any PULSE_SERVER style address) instead of the local
+    /// daemon. `pactl` calls made afterwards are pointed at the same server.
+    #[napi]
+    pub fn initialize_with_server(&mut self, server: Option<String>) -> Result<(), BeacnError> {
+        self.connect_task(server).connect()
+    }
 
-        *self.pulse_context.lock().unwrap() = Some(context);
-        true
+    /// Same as `initialize`, but waits for the server on the libuv threadpool so a
+    /// slow or remote server doesn't stall the event loop. Resolves to whether the
+    /// connection succeeded.
+    #[napi(ts_return_type = "Promise<boolean>")]
+    pub fn initialize_async(&self, server: Option<String>) -> AsyncTask<ConnectTask> {
+        AsyncTask::new(self.connect_task(server))
     }
 
+    /// Disconnects from the server. Calls that change the server return
+    /// `NotInitialized` until `initialize` is run again. The read-only listings
+    /// (`get_audio_devices`, `list_cards`, `list_routes`, `get_routing_table`,
+    /// `get_module_id`, `is_module_loaded` and the like) keep answering through
+    /// `pactl`.
     #[napi]
-    pub fn get_audio_devices(&self) -> Vec<AudioDevice> {
-        let mut devices = Vec::new();
+    pub fn shutdown(&mut self) -> Result<(), BeacnError> {
+        // Joins the background threads; the pulse ones hold their own connections
+        self.device_watcher.lock().take();
+        self.auto_restore.lock().take();
+        self.meters.lock().clear();
+        self.fades.lock().clear();
+        self.tones.lock().clear();
+        self.recordings.lock().clear();
+        self.playbacks.lock().clear();
+        // Stopping an unmute unmutes early rather than leaving the route silent
+        self.route_unmutes.lock().clear();
 
-        // Use pulseaudio command line to list sinks
-        if let Ok(output) = std::process::Command::new("pactl")
-            .args(["list", "short", "sinks"])
-            .output() {
-                if let Ok(output_str) = String::from_utf8(output.stdout) {
-                    for line in output_str.lines() {
-                        let parts: Vec<&str> = line.split('\t').collect();
-                        if parts.len() >= 2 {
-                            devices.push(AudioDevice {
-                                name: parts[1].to_string(),
-                                id: parts[0].to_string(),
-                                description: parts.get(2).unwrap_or(&"").to_string(),
-                                is_output: true,
-                            });
-                        }
+        let mut context = self.pulse_context.lock().take().ok_or(BeacnError::NotInitialized)?;
+        context.disconnect();
+        drop(context);
+
+        // Nothing is left registered on the mainloop once the context is gone
+        if let Some(mut mainloop) = self.pulse_mainloop.lock().take() {
+            mainloop.quit(pulse::def::Retval(0));
+        }
+
+        *self.backend.lock() = Backend::Unknown;
+        Ok(())
+    }
+
+    /// Calls `callback` with the fresh device list whenever a sink, source or card is
+    /// added or removed. Replaces any previously registered callback.
+    #[napi(ts_args_type = "callback: (devices: Array<AudioDevice>) => void")]
+    pub fn on_devices_changed(&self, callback: JsFunction) -> Result<(), BeacnError> {
+        self.ensure_initialized()?;
+
+        let tsfn: ThreadsafeFunction<Vec<AudioDevice>, ErrorStrategy::Fatal> = callback
+            .create_threadsafe_function(0, |ctx: ThreadSafeCallContext<Vec<AudioDevice>>| Ok(vec![ctx.value]))
+            .map_err(|e| BeacnError::InvalidArgument(e.reason))?;
+
+        let server = pulse_server();
+        let iterations = self.connect_iterations;
+
+        // Stop the old watcher before starting its replacement
+        let mut watcher = self.device_watcher.lock();
+        watcher.take();
+        *watcher = Some(Worker::spawn(move |stop| {
+            // The standard mainloop is !Send, so the watcher opens its own connection
+            let (mut mainloop, mut context) = match connect_context(server.as_deref(), iterations) {
+                Ok(c) => c,
+                Err(e) => {
+                    logging::error(format_args!("Device watcher could not connect: {}", e));
+                    return;
+                }
+            };
+
+            let changed = Rc::new(Cell::new(false));
+            let flag = changed.clone();
+            context.set_subscribe_callback(Some(Box::new(move |facility, operation, _index| {
+                let device = matches!(facility, Some(Facility::Sink | Facility::Source | Facility::Card));
+                let added_or_removed = matches!(operation, Some(Operation::New | Operation::Removed));
+                if device && added_or_removed {
+                    flag.set(true);
+                }
+            })));
+            context.subscribe(InterestMaskSet::SINK | InterestMaskSet::SOURCE | InterestMaskSet::CARD, |_| {});
+
+            while !stop.load(Ordering::Relaxed) {
+                match mainloop.iterate(false) {
+                    IterateResult::Quit(_) | IterateResult::Err(_) => break,
+                    IterateResult::Success(_) => {}
+                }
+
+                if changed.replace(false) {
+                    let devices = native_devices(&mut mainloop, &context).unwrap_or_else(|_| list_devices());
+                    tsfn.call(devices, ThreadsafeFunctionCallMode::NonBlocking);
+                }
+                std::thread::sleep(std::time::Duration::from_millis(WATCH_POLL_MS));
+            }
+
+            context.disconnect();
+        }));
+
+        Ok(())
+    }
+
+    /// Sends the crate's log records to `callback` as `{ level, message }` instead of
+    /// stderr. Logging is shared by every instance, so the latest callback wins.
+    #[napi(ts_args_type = "callback: (record: LogRecord) => void")]
+    pub fn set_log_callback(&self, env: Env, callback: JsFunction) -> Result<(), BeacnError> {
+        let mut tsfn: ThreadsafeFunction<LogRecord, ErrorStrategy::Fatal> = callback
+            .create_threadsafe_function(0, |ctx: ThreadSafeCallContext<LogRecord>| Ok(vec![ctx.value]))
+            .map_err(|e| BeacnError::InvalidArgument(e.reason))?;
+        // A log sink alone shouldn't keep Node running
+        tsfn.unref(&env).map_err(|e| BeacnError::InvalidArgument(e.reason))?;
+
+        logging::set_log_callback(Some(tsfn));
+        Ok(())
+    }
+
+    /// Most verbose level logged; `Warn` by default. `Debug` also logs every command run.
+    #[napi]
+    pub fn set_log_level(&self, level: LogLevel) {
+        logging::set_log_level(level);
+    }
+
+    /// Watches for the sound server restarting (its cookie changes) and then
+    /// reconnects and loads every tracked device and route again, as `reconnect`
+    /// does. Checks about once a second until `disable_auto_restore` or `shutdown`.
+    #[napi]
+    pub fn enable_auto_restore(&self) -> Result<(), BeacnError> {
+        self.ensure_initialized()?;
+
+        let connect = self.connect_task(pulse_server());
+        let own_modules = self.own_modules.clone();
+        let own_links = self.own_links.clone();
+
+        let mut restore = self.auto_restore.lock();
+        restore.take();
+        *restore = Some(Worker::spawn(move |stop| {
+            let cookie = || run_pactl(&["info"]).ok().and_then(|info| parse_info(&info).remove("Cookie"));
+            let mut known = cookie();
+
+            while !stop.load(Ordering::Relaxed) {
+                for _ in 0..AUTO_RESTORE_POLL_MS / WATCH_POLL_MS {
+                    if stop.load(Ordering::Relaxed) {
+                        return;
+                    }
+                    std::thread::sleep(std::time::Duration::from_millis(WATCH_POLL_MS));
+                }
+
+                // While the server is down there is no cookie; wait for the new one
+                let Some(current) = cookie() else { continue };
+                if known.as_ref() == Some(&current) {
+                    continue;
+                }
+
+                if known.is_some() {
+                    logging::info(format_args!("Sound server restarted, restoring BEACN Link devices"));
+                    let restored = connect.connect().and_then(|()| restore_modules(&own_modules));
+                    if let Err(e) = restored {
+                        logging::error(format_args!("Could not restore after a server restart: {}", e));
+                        continue;
                     }
+                    restore_links(&own_links);
                 }
+                known = Some(current);
+            }
+        }));
+
+        Ok(())
+    }
+
+    #[napi]
+    pub fn disable_auto_restore(&self) {
+        self.auto_restore.lock().take();
+    }
+
+    /// Streams peak/RMS levels of `sink`'s monitor to `callback` at up to ~30 Hz.
+    /// Restarts the meter if one is already running for this sink.
+    #[napi(ts_args_type = "sink: string, callback: (level: MeterLevel) => void")]
+    pub fn start_metering(&self, sink: String, callback: JsFunction) -> Result<(), BeacnError> {
+        let monitor = self.get_monitor_source(sink.clone())?;
+
+        let tsfn: ThreadsafeFunction<MeterLevel, ErrorStrategy::Fatal> = callback
+            .create_threadsafe_function(0, |ctx: ThreadSafeCallContext<MeterLevel>| Ok(vec![ctx.value]))
+            .map_err(|e| BeacnError::InvalidArgument(e.reason))?;
+
+        let server = pulse_server();
+        let iterations = self.connect_iterations;
+
+        let mut meters = self.meters.lock();
+        meters.remove(&sink);
+        meters.insert(sink, Worker::spawn(move |stop| run_meter(monitor, server, iterations, tsfn, stop)));
+        Ok(())
+    }
+
+    #[napi]
+    pub fn stop_metering(&self, sink: String) -> Result<(), BeacnError> {
+        self.meters
+            .lock()
+            .remove(&sink)
+            .map(drop)
+            .ok_or_else(|| BeacnError::NotFound(format!("meter for '{}'", sink)))
+    }
+
+    /// Records what `sink` plays to a WAV file at `path`, in the sink's own rate,
+    /// channel count and (little endian) sample format. Stops by itself after
+    /// `duration_ms` if given, otherwise when `stop_recording` is called or the file
+    /// reaches the 4 GiB a WAV can hold. A duration past that limit is rejected.
+    #[napi]
+    pub fn record_sink(&self, sink: String, path: String, duration_ms: Option<u32>) -> Result<RecordingHandle, BeacnError> {
+        let monitor = self.get_monitor_source(sink.clone())?;
+
+        let (format, channels, rate) = find_sink(&sink)?
+            .field("Sample Specification")
+            .and_then(parse_sample_spec)
+            .ok_or_else(|| BeacnError::NotFound(format!("sample specification of sink '{}'", sink)))?;
+        let wav = WavFormat::for_sink(&format, channels, rate);
+        let limit = wav.data_limit(duration_ms)?;
+        let file = create_wav(&path, &wav)?;
+
+        let handle = RecordingHandle {
+            id: self.next_handle_id.fetch_add(1, Ordering::Relaxed),
+            path,
+            sample_rate: rate,
+            channels,
+            sample_format: wav.name.to_string(),
+        };
+
+        let server = pulse_server();
+        let iterations = self.connect_iterations;
+        self.recordings.lock().insert(
+            handle.id,
+            Worker::spawn(move |stop| run_recording(monitor, server, iterations, wav, file, limit, stop)),
+        );
+        Ok(handle)
+    }
+
+    /// Ends a recording and finalizes its file. Also succeeds for a timed recording that
+    /// already finished on its own.
+    #[napi]
+    pub fn stop_recording(&self, handle: RecordingHandle) -> Result<(), BeacnError> {
+        self.recordings
+            .lock()
+            .remove(&handle.id)
+            .map(drop)
+            .ok_or_else(|| BeacnError::NotFound(format!("recording {}", handle.id)))
+    }
+
+    /// Beeps a sine at `freq_hz` for `duration_ms` on `sink`, e.g. to tell outputs apart
+    /// while wiring routes. Returns once the tone has started; a new tone on the same
+    /// sink cuts the previous one off.
+    #[napi]
+    pub fn play_test_tone(&self, sink: String, freq_hz: u32, duration_ms: u32, volume_percent: u32) -> Result<(), BeacnError> {
+        self.ensure_initialized()?;
+
+        if !TONE_FREQ_RANGE_HZ.contains(&freq_hz) {
+            return Err(BeacnError::InvalidArgument(format!(
+                "{}Hz is outside {}-{}Hz",
+                freq_hz,
+                TONE_FREQ_RANGE_HZ.start(),
+                TONE_FREQ_RANGE_HZ.end()
+            )));
         }
 
-        // Also list sources
-        if let Ok(output) = std::process::Command::new("pactl")
-            .args(["list", "short", "sources"])
-            .output() {
-                if let Ok(output_str) = String::from_utf8(output.stdout) {
-                    for line in output_str.lines() {
-                        let parts: Vec<&str> = line.split('\t').collect();
-                        if parts.len() >= 2 {
-                            devices.push(AudioDevice {
-                                name: parts[1].to_string(),
-                                id: parts[0].to_string(),
-                                description: parts.get(2).unwrap_or(&"").to_string(),
-                                is_output: false,
-                            });
-                        }
+        if duration_ms == 0 || duration_ms > MAX_TONE_DURATION_MS {
+            return Err(BeacnError::InvalidArgument(format!(
+                "tone duration {}ms is outside 1-{}ms",
+                duration_ms, MAX_TONE_DURATION_MS
+            )));
+        }
+
+        // Anything above full scale would just clip
+        if volume_percent > 100 {
+            return Err(BeacnError::InvalidArgument(format!("tone volume {}% exceeds 100%", volume_percent)));
+        }
+
+        if !self.device_exists(&sink, true) {
+            return Err(BeacnError::NotFound(format!("sink '{}'", sink)));
+        }
+
+        let samples = sine_samples(freq_hz, duration_ms, volume_percent as f64 / 100.0);
+        let server = pulse_server();
+        let iterations = self.connect_iterations;
+
+        let mut tones = self.tones.lock();
+        tones.remove(&sink);
+        let target = sink.clone();
+        tones.insert(sink, Worker::spawn(move |stop| run_tone(target, server, iterations, samples, stop)));
+        Ok(())
+    }
+
+    /// Plays the audio file at `path` (anything `paplay` can decode, e.g. WAV or FLAC)
+    /// to `sink` until it ends or `stop_playback` is called.
+    #[napi]
+    pub fn play_file(&self, sink: String, path: String) -> Result<PlaybackHandle, BeacnError> {
+        self.ensure_initialized()?;
+
+        if !self.device_exists(&sink, true) {
+            return Err(BeacnError::NotFound(format!("sink '{}'", sink)));
+        }
+
+        if !std::path::Path::new(&path).is_file() {
+            return Err(BeacnError::NotFound(format!("file '{}'", path)));
+        }
+
+        let mut child = spawn_command("paplay", &[&format!("--device={}", sink), &path])?;
+        let handle = PlaybackHandle { id: self.next_handle_id.fetch_add(1, Ordering::Relaxed), sink, path };
+
+        self.playbacks.lock().insert(
+            handle.id,
+            Worker::spawn(move |stop| {
+                while !stop.load(Ordering::Relaxed) {
+                    if !matches!(child.try_wait(), Ok(None)) {
+                        return;
                     }
+                    std::thread::sleep(std::time::Duration::from_millis(WATCH_POLL_MS));
                 }
+                let _ = child.kill();
+                let _ = child.wait();
+            }),
+        );
+        Ok(handle)
+    }
+
+    /// Stops a playback started with `play_file`. Also succeeds once it has finished.
+    #[napi]
+    pub fn stop_playback(&self, handle: PlaybackHandle) -> Result<(), BeacnError> {
+        self.playbacks
+            .lock()
+            .remove(&handle.id)
+            .map(drop)
+            .ok_or_else(|| BeacnError::NotFound(format!("playback {}", handle.id)))
+    }
+
+    /// True while the stored context is connected and ready.
+    #[napi]
+    pub fn is_initialized(&self) -> bool {
+        self.get_context_state() == "ready"
+    }
+
+    /// The stored context's state: `"unconnected"`, `"connecting"`, `"authorizing"`,
+    /// `"setting_name"`, `"ready"`, `"failed"` or `"terminated"`. Reports
+    /// `"unconnected"` before `initialize` and after `shutdown`.
+    #[napi]
+    pub fn get_context_state(&self) -> String {
+        // The context only learns about a dropped connection when its mainloop runs
+        if let Some(mainloop) = self.pulse_mainloop.lock().as_mut() {
+            mainloop.iterate(false);
         }
 
-        devices
+        let state = match self.pulse_context.lock().as_ref() {
+            None => return "unconnected".to_string(),
+            Some(context) => context.get_state(),
+        };
+
+        match state {
+            State::Unconnected => "unconnected",
+            State::Connecting => "connecting",
+            State::Authorizing => "authorizing",
+            State::SettingName => "setting_name",
+            State::Ready => "ready",
+            State::Failed => "failed",
+            State::Terminated => "terminated",
+        }
+        .to_string()
     }
 
+    /// Drops the current connection and connects again to the same server, e.g. after
+    /// PulseAudio restarted on resume. Tracked modules are then re-validated against
+    /// the new server by index, module name and arguments; any that are gone are
+    /// loaded again and their IDs updated, so IDs handed out earlier may change.
     #[napi]
-    pub fn create_virtual_output(&self, name: String) -> bool {
-        // Create virtual output device using PulseAudio module-null-sink
-        if let Some(_ctx) = self.pulse_context.lock().unwrap().as_ref() {
-            let output = std::process::Command::new("pactl")
-                .args([
-                    "load-module",
-                    "module-null-sink",
-                    &format!("sink_name={}", name),
-                    &format!("sink_properties=device.description=\"{}\"", name),
-                ])
-                .output();
-
-            match output {
-                Ok(out) => out.status.success(),
-                Err(_) => false,
-            }
-        } else {
-            false
-        }
-    }
-
-    #[napi]
-    pub fn route_audio(&self, source: String, destination: String) -> bool {
-        if let Some(_ctx) = self.pulse_context.lock().unwrap().as_ref() {
-            let output = std::process::Command::new("pactl")
-                .args([
-                    "load-module",
-                    "module-loopback",
-                    &format!("source={}", source),
-                    &format!("sink={}", destination),
-                ])
-                .output();
-
-            match output {
-                Ok(out) => out.status.success(),
-                Err(_) => false,
-            }
-        } else {
-            false
-        }
-    }
-
-    #[napi] 
-    pub fn create_link_outputs(&self) -> bool {
-        // Create the 4 BEACN Link outputs
-        let output_names = [
-            "BEACN_Link_Out",
-            "BEACN_Link_2_Out", 
-            "BEACN_Link_3_Out",
-            "BEACN_Link_4_Out"
-        ];
+    pub fn reconnect(&mut self) -> Result<(), BeacnError> {
+        if let Some(mut context) = self.pulse_context.lock().take() {
+            context.disconnect();
+        }
+        self.pulse_mainloop.lock().take();
+
+        self.initialize_with_server(pulse_server())?;
+        self.restore_tracked_modules()
+    }
+
+    /// `"pulseaudio"`, `"pipewire"`, or `"unknown"` before a successful `initialize`.
+    #[napi]
+    pub fn get_backend(&self) -> Backend {
+        *self.backend.lock()
+    }
+
+    /// What works on the detected backend, so a UI can hide the rest. Checked on each
+    /// call, since tools and plugins can be installed while the app runs.
+    #[napi]
+    pub fn capabilities(&self) -> Capabilities {
+        let backend = *self.backend.lock();
+        let pipewire = backend == Backend::PipeWire;
+
+        Capabilities {
+            backend,
+            pw_link: pipewire && command_succeeds("pw-link", &["--version"]),
+            equalizer: ladspa_plugin_installed(EQ_PLUGIN),
+            wpctl_defaults: pipewire && command_succeeds("wpctl", &["status"]),
+        }
+    }
+
+    /// BEACN devices plugged in over USB, found by vendor ID in sysfs. Doesn't need
+    /// `initialize`.
+    #[napi]
+    pub fn detect_beacn_hardware(&self) -> Vec<BeacnDevice> {
+        scan_beacn_devices(std::path::Path::new(USB_DEVICES_DIR))
+    }
+
+    /// Checks the environment step by step so a failing setup says why: whether `pactl`
+    /// runs, the server answers, a throwaway null sink can be loaded and unloaded, and
+    /// this instance's connection is ready. Later checks are skipped once one fails.
+    #[napi]
+    pub fn self_test(&self) -> SelfTestReport {
+        let mut checks = Vec::new();
+        let mut check = |name: &str, result: Result<String, BeacnError>| {
+            let passed = result.is_ok();
+            let message = result.unwrap_or_else(|e| e.to_string());
+            checks.push(SelfTestCheck { name: name.to_string(), passed, message });
+            passed
+        };
+
+        let _ = check("pactl", run_pactl(&["--version"]).map(|v| v.lines().next().unwrap_or("").to_string()))
+            && check(
+                "server",
+                run_pactl(&["info"]).map(|info| parse_info(&info).remove("Server Name").unwrap_or_default()),
+            )
+            && check(
+                "load_module",
+                load_module_untracked("module-null-sink", &[&format!("sink_name={}", SELF_TEST_SINK)]).and_then(|id| {
+                    run_pactl(&["unload-module", &id.to_string()])?;
+                    Ok(format!("loaded and unloaded module {}", id))
+                }),
+            )
+            && check(
+                "mainloop",
+                match self.get_context_state().as_str() {
+                    "ready" => Ok("context is ready".to_string()),
+                    "unconnected" => Err(BeacnError::NotInitialized),
+                    state => Err(BeacnError::ConnectionFailed(format!("context is {}", state))),
+                },
+            );
+
+        SelfTestReport { passed: checks.iter().all(|c| c.passed), checks }
+    }
+
+    #[napi]
+    pub fn get_server_info(&self) -> Result<ServerInfo, BeacnError> {
+        self.ensure_initialized()?;
+
+        let mut info = self.server_info()?;
+        let mut take = |key: &str| info.remove(key).unwrap_or_default();
+
+        Ok(ServerInfo {
+            server_name: take("Server Name"),
+            server_version: take("Server Version"),
+            default_sample_spec: take("Default Sample Specification"),
+            default_channel_map: take("Default Channel Map"),
+            // Same `n/a` handling as get_default_sink
+            default_sink: Some(take("Default Sink")).filter(|n| !n.is_empty() && n != "n/a"),
+            default_source: Some(take("Default Source")).filter(|n| !n.is_empty() && n != "n/a"),
+        })
+    }
+
+    /// Every sink and source, asked of the server over this instance's connection.
+    /// Before `initialize`, or if that query fails, the `pactl` listings are used.
+    #[napi]
+    pub fn get_audio_devices(&self) -> Vec<AudioDevice> {
+        match self.with_connection(native_devices) {
+            Ok(devices) => devices,
+            Err(e) => {
+                logging::debug(format_args!("Listing devices through pactl: {}", e));
+                list_devices()
+            }
+        }
+    }
+
+    /// Only the devices BEACN Link made: anything named with the `BEACN_Link` prefix,
+    /// plus sinks and sources loaded by this instance under other names (and their
+    /// monitors).
+    #[napi]
+    pub fn get_beacn_devices(&self) -> Vec<AudioDevice> {
+        let tracked: Vec<String> = self
+            .own_modules
+            .lock()
+            .iter()
+            .flat_map(|m| parse_module_args(&m.args.join(" ")))
+            .filter(|(key, _)| key == "sink_name" || key == "source_name")
+            .map(|(_, name)| name)
+            .collect();
+
+        self.get_audio_devices()
+            .into_iter()
+            .filter(|d| {
+                d.name.starts_with(DEFAULT_LINK_PREFIX)
+                    || tracked.contains(&d.name)
+                    || d.monitor_of.as_ref().is_some_and(|sink| tracked.contains(sink))
+            })
+            .collect()
+    }
+
+    /// Same as `get_audio_devices`, but runs on the libuv threadpool so polling
+    /// doesn't block the event loop.
+    #[napi]
+    pub fn get_audio_devices_async(&self) -> AsyncTask<ListDevicesTask> {
+        AsyncTask::new(ListDevicesTask { server: pulse_server(), iterations: self.connect_iterations })
+    }
+
+    /// Like `get_audio_devices` but parsed from the long listings, so each entry carries
+    /// its format, state and volume. Costs the same two `pactl` calls.
+    #[napi]
+    pub fn get_audio_devices_detailed(&self) -> Vec<AudioDeviceDetail> {
+        let mut devices = Vec::new();
+
+        for (kind, header, is_output) in [("sinks", "Sink", true), ("sources", "Source", false)] {
+            let output = match run_pactl(&["list", kind]) {
+                Ok(o) => o,
+                Err(_) => continue,
+            };
 
-        for name in output_names.iter() {
-            if !self.create_virtual_output(name.to_string()) {
-                return false;
+            for block in parse_blocks(&output, header) {
+                let (sample_format, channels, sample_rate) = block
+                    .field("Sample Specification")
+                    .and_then(parse_sample_spec)
+                    .unwrap_or_default();
+
+                devices.push(AudioDeviceDetail {
+                    index: block.index,
+                    name: block.name().unwrap_or("").to_string(),
+                    description: block.field("Description").unwrap_or("").to_string(),
+                    kind: match block.field("Monitor of Sink") {
+                        _ if is_output => DeviceKind::Sink,
+                        Some(sink) if sink != "n/a" => DeviceKind::MonitorSource,
+                        _ => DeviceKind::Source,
+                    },
+                    is_output,
+                    channels,
+                    sample_rate,
+                    sample_format,
+                    state: block.field("State").unwrap_or("").to_string(),
+                    volume_percent: average_percent(&parse_channel_percents(block.field("Volume").unwrap_or(""))),
+                    muted: parse_yes_no(block.field("Mute")),
+                    monitor_source: block.field("Monitor Source").map(str::to_string),
+                });
             }
         }
 
-        true
+        devices
+    }
+
+    /// Creates a null sink called `name`. Pass `suspend_on_idle: false` to keep it
+    /// running while silent, avoiding the pop and delay when playback starts.
+    #[napi]
+    pub fn create_virtual_output(
+        &self,
+        name: String,
+        format: Option<SinkFormat>,
+        suspend_on_idle: Option<bool>,
+    ) -> Result<u32, BeacnError> {
+        // Create virtual output device using PulseAudio module-null-sink
+        self.ensure_initialized()?;
+        validate_sink_name(&name)?;
+
+        if self.device_exists(&name, true) {
+            return Err(BeacnError::DuplicateName(name));
+        }
+
+        let extra: &[&str] = if suspend_on_idle == Some(false) { &NO_IDLE_SUSPEND_PROPERTIES } else { &[] };
+        let mut args = vec![format!("sink_name={}", name), properties_arg("sink_properties", &name, extra)?];
+        if let Some(format) = &format {
+            args.extend(format.module_args()?);
+        }
+
+        let args: Vec<&str> = args.iter().map(String::as_str).collect();
+        self.load_tracked_module("module-null-sink", &args)
+    }
+
+    /// Like `create_virtual_output`, but if `base_name` is taken it picks the lowest free
+    /// `base_name_2`, `base_name_3`, ... instead of failing.
+    #[napi]
+    pub fn create_virtual_output_unique(&self, base_name: String) -> Result<CreatedOutput, BeacnError> {
+        self.ensure_initialized()?;
+        validate_sink_name(&base_name)?;
+
+        let sinks: Vec<String> = self.get_audio_devices().into_iter().filter(|d| d.is_output()).map(|d| d.name).collect();
+        let name = std::iter::once(base_name.clone())
+            .chain((2..).map(|n| format!("{}_{}", base_name, n)))
+            .find(|candidate| !sinks.contains(candidate))
+            .unwrap_or(base_name);
+
+        let module_id = self.create_virtual_output(name.clone(), None, None)?;
+        Ok(CreatedOutput { name, module_id })
+    }
+
+    /// Changes the description pavucontrol shows for a virtual output. Neither server
+    /// can update a sink's properties live, so the sink is recreated under the same
+    /// name: streams playing to it move to the fallback sink, and routes BEACN Link
+    /// made through it are loaded again afterwards. Only sinks made by
+    /// `create_virtual_output` work; if the new sink fails to load, the old one is put
+    /// back.
+    #[napi]
+    pub fn set_sink_description(&self, sink: String, description: String) -> Result<(), BeacnError> {
+        self.ensure_initialized()?;
+
+        self.reload_tracked_sink("module-null-sink", &sink, |args| {
+            let Some(position) = args.iter().position(|a| a.starts_with("sink_properties=")) else {
+                return Err(BeacnError::Unsupported(format!("changing the description of '{}'", sink)));
+            };
+            let extra: &[&str] =
+                if args[position].contains(NO_IDLE_SUSPEND_PROPERTIES[0]) { &NO_IDLE_SUSPEND_PROPERTIES } else { &[] };
+            args[position] = properties_arg("sink_properties", &description, extra)?;
+            Ok(())
+        })?;
+        Ok(())
+    }
+
+    /// Adds `member` to a combined sink made by `create_combined_sink`. module-combine-sink
+    /// can't change its members while loaded, so this reloads it, which the result
+    /// reports: streams on it move to the fallback sink, and routes BEACN Link made
+    /// through it are loaded again afterwards. If the new sink fails to load, the old
+    /// one is put back.
+    #[napi]
+    pub fn combine_sink_add_member(&self, combined_sink: String, member: String) -> Result<CombinedSinkUpdate, BeacnError> {
+        self.ensure_initialized()?;
+
+        if !self.device_exists(&member, true) {
+            return Err(BeacnError::NotFound(format!("member sink '{}'", member)));
+        }
+
+        self.reload_combined_sink(&combined_sink, |members| {
+            if members.contains(&member) {
+                return Err(BeacnError::DuplicateName(member));
+            }
+            members.push(member);
+            Ok(())
+        })
+    }
+
+    /// Drops `member` from a combined sink, reloading it the same way as
+    /// `combine_sink_add_member`. The last member can't be removed.
+    #[napi]
+    pub fn combine_sink_remove_member(&self, combined_sink: String, member: String) -> Result<CombinedSinkUpdate, BeacnError> {
+        self.ensure_initialized()?;
+
+        self.reload_combined_sink(&combined_sink, |members| {
+            let Some(position) = members.iter().position(|m| *m == member) else {
+                return Err(BeacnError::NotFound(format!("member sink '{}' of '{}'", member, combined_sink)));
+            };
+            // With no slaves module-combine-sink would grab every sink instead
+            if members.len() == 1 {
+                return Err(BeacnError::InvalidArgument(format!("'{}' is the last member of '{}'", member, combined_sink)));
+            }
+            members.remove(position);
+            Ok(())
+        })
+    }
+
+    /// Creates a null sink with the channel layout of `layout`. Rate and sample format
+    /// are left to the server's defaults.
+    #[napi]
+    pub fn create_surround_sink(&self, name: String, layout: SurroundLayout) -> Result<u32, BeacnError> {
+        self.ensure_initialized()?;
+        validate_sink_name(&name)?;
+
+        if self.device_exists(&name, true) {
+            return Err(BeacnError::DuplicateName(name));
+        }
+
+        let (channels, channel_map) = layout.channel_map();
+        self.load_tracked_module(
+            "module-null-sink",
+            &[
+                &format!("sink_name={}", name),
+                &description_arg("sink_properties", &name)?,
+                &format!("channels={}", channels),
+                &format!("channel_map={}", channel_map),
+            ],
+        )
+    }
+
+    /// Puts a 15 band graphic EQ in front of `master_sink`: a new `<master>_EQ` sink
+    /// that filters what it receives and plays it to the master. `bands` are gains in
+    /// dB (-70 to 30), lowest band first. Needs the `mbeq_1197` LADSPA plugin from
+    /// swh-plugins; without it this fails with `NotFound`.
+    #[napi]
+    pub fn attach_equalizer(&self, master_sink: String, bands: Vec<f64>) -> Result<EqHandle, BeacnError> {
+        self.ensure_initialized()?;
+
+        if bands.len() != EQ_BAND_COUNT {
+            return Err(BeacnError::InvalidArgument(format!(
+                "the equalizer has {} bands, got {} gains",
+                EQ_BAND_COUNT,
+                bands.len()
+            )));
+        }
+
+        if let Some(gain) = bands.iter().find(|g| !EQ_GAIN_RANGE_DB.contains(*g)) {
+            return Err(BeacnError::InvalidArgument(format!(
+                "gain {}dB is outside {}-{}dB",
+                gain,
+                EQ_GAIN_RANGE_DB.start(),
+                EQ_GAIN_RANGE_DB.end()
+            )));
+        }
+
+        if !ladspa_plugin_installed(EQ_PLUGIN) {
+            return Err(BeacnError::NotFound(format!("LADSPA plugin {} (install swh-plugins)", EQ_PLUGIN)));
+        }
+
+        if !self.device_exists(&master_sink, true) {
+            return Err(BeacnError::NotFound(format!("sink '{}'", master_sink)));
+        }
+
+        let sink_name = format!("{}_EQ", master_sink);
+        validate_sink_name(&sink_name)?;
+        if self.device_exists(&sink_name, true) {
+            return Err(BeacnError::DuplicateName(sink_name));
+        }
+
+        let controls: Vec<String> = bands.iter().map(|g| g.to_string()).collect();
+        let module_id = self.load_tracked_module(
+            "module-ladspa-sink",
+            &[
+                &format!("sink_name={}", sink_name),
+                &description_arg("sink_properties", &format!("{} EQ", master_sink))?,
+                &format!("sink_master={}", master_sink),
+                &format!("plugin={}", EQ_PLUGIN),
+                &format!("label={}", EQ_LABEL),
+                &format!("control={}", controls.join(",")),
+            ],
+        )?;
+
+        Ok(EqHandle { module_id, sink_name, master_sink })
+    }
+
+    #[napi]
+    pub fn detach_equalizer(&self, handle: EqHandle) -> Result<(), BeacnError> {
+        self.unload_module(handle.module_id)
+    }
+
+    /// Creates `name` as a `module-tunnel-sink` that streams whatever it receives to the
+    /// default sink of the PulseAudio server at `server`, e.g. `tcp:studio-pc:4713`.
+    #[napi]
+    pub fn create_tunnel_sink(&self, name: String, server: String) -> Result<u32, BeacnError> {
+        self.create_tunnel("module-tunnel-sink", true, name, server)
+    }
+
+    /// The reverse of `create_tunnel_sink`: a local source `name` carrying the remote
+    /// server's default source.
+    #[napi]
+    pub fn create_tunnel_source(&self, name: String, server: String) -> Result<u32, BeacnError> {
+        self.create_tunnel("module-tunnel-source", false, name, server)
+    }
+
+    /// Streams `source` over RTP to `destination_ip` (unicast or multicast) on `port`,
+    /// announced over SAP so `start_rtp_recv` on other machines picks it up.
+    #[napi]
+    pub fn start_rtp_send(&self, source: String, destination_ip: String, port: u16) -> Result<u32, BeacnError> {
+        self.ensure_initialized()?;
+        parse_ip(&destination_ip)?;
+
+        if !self.device_exists(&source, false) {
+            return Err(BeacnError::NotFound(format!("source '{}'", source)));
+        }
+
+        self.load_tracked_module(
+            "module-rtp-send",
+            &[&format!("source={}", source), &format!("destination_ip={}", destination_ip), &format!("port={}", port)],
+        )
+    }
+
+    /// Plays RTP streams announced on `listen_ip` into a new null sink named
+    /// `BEACN_Link_RTP_<port>`, which can then be routed like any other output.
+    /// `module-rtp-recv` finds streams by their SAP announcements rather than by port,
+    /// so `port` only names the sink. Returns the receiver's module ID; the sink is
+    /// tracked too.
+    #[napi]
+    pub fn start_rtp_recv(&self, listen_ip: String, port: u16) -> Result<u32, BeacnError> {
+        self.ensure_initialized()?;
+        parse_ip(&listen_ip)?;
+
+        let sink_name = format!("{}_RTP_{}", DEFAULT_LINK_PREFIX, port);
+        let sink_id = self.create_virtual_output(sink_name.clone(), None, None)?;
+
+        let recv_id =
+            self.load_tracked_module("module-rtp-recv", &[&format!("sink={}", sink_name), &format!("sap_address={}", listen_ip)]);
+
+        if recv_id.is_err() {
+            let _ = self.unload_tracked_module(sink_id);
+        }
+        recv_id
+    }
+
+    /// Creates `name` as a `module-pipe-sink` that writes what it plays to the FIFO at
+    /// `path`, created by the server if missing. The parent directory must exist.
+    #[napi]
+    pub fn create_pipe_sink(&self, name: String, path: String) -> Result<u32, BeacnError> {
+        self.create_pipe("module-pipe-sink", true, name, path)
+    }
+
+    /// Creates `name` as a `module-pipe-source` that reads raw audio from the FIFO at
+    /// `path`, e.g. an ffmpeg process writing `-f s16le`.
+    #[napi]
+    pub fn create_pipe_source(&self, name: String, path: String) -> Result<u32, BeacnError> {
+        self.create_pipe("module-pipe-source", false, name, path)
+    }
+
+    /// Creates `name` as a `module-combine-sink` that plays to every sink in `member_sinks`.
+    #[napi]
+    pub fn create_combined_sink(&self, name: String, member_sinks: Vec<String>) -> Result<u32, BeacnError> {
+        self.ensure_initialized()?;
+        validate_sink_name(&name)?;
+
+        if member_sinks.is_empty() {
+            return Err(BeacnError::InvalidArgument("a combined sink needs at least one member sink".to_string()));
+        }
+
+        if self.device_exists(&name, true) {
+            return Err(BeacnError::DuplicateName(name));
+        }
+
+        if let Some(missing) = member_sinks.iter().find(|m| !self.device_exists(m, true)) {
+            return Err(BeacnError::NotFound(format!("member sink '{}'", missing)));
+        }
+
+        self.load_tracked_module(
+            "module-combine-sink",
+            &[
+                &format!("sink_name={}", name),
+                &description_arg("sink_properties", &name)?,
+                &format!("slaves={}", member_sinks.join(",")),
+            ],
+        )
+    }
+
+    /// Creates `name` as a `module-remap-sink` over `master`. Channel `i` of
+    /// `channel_map` plays to channel `i` of `master_channel_map`, e.g. `mono` over
+    /// `aux2` to feed one channel of a 4-channel output on its own.
+    #[napi]
+    pub fn create_remap_sink(
+        &self,
+        name: String,
+        master: String,
+        channels: u8,
+        channel_map: String,
+        master_channel_map: String,
+    ) -> Result<u32, BeacnError> {
+        self.create_remap("module-remap-sink", true, name, master, channels, channel_map, master_channel_map)
+    }
+
+    /// Creates `name` as a `module-remap-source` over `master`, e.g. `mono` over
+    /// `front-left` to expose only the left input of a stereo interface as a mic.
+    #[napi]
+    pub fn create_remap_source(
+        &self,
+        name: String,
+        master: String,
+        channels: u8,
+        channel_map: String,
+        master_channel_map: String,
+    ) -> Result<u32, BeacnError> {
+        self.create_remap("module-remap-source", false, name, master, channels, channel_map, master_channel_map)
+    }
+
+    /// Creates a virtual microphone: a null sink named `<name>_Sink` whose monitor is
+    /// re-exposed as a proper source called `<name>`. Route audio into `<name>_Sink`
+    /// and apps can record it from `<name>`. Returns the source's module ID; the backing
+    /// sink is tracked too and cleaned up with the rest.
+    #[napi]
+    pub fn create_virtual_source(&self, name: String) -> Result<u32, BeacnError> {
+        self.ensure_initialized()?;
+        validate_sink_name(&name)?;
+        let sink_name = format!("{}{}", name, VIRTUAL_SOURCE_SINK_SUFFIX);
+        validate_sink_name(&sink_name)?;
+
+        if self.device_exists(&name, false) {
+            return Err(BeacnError::DuplicateName(name));
+        }
+
+        let sink_id = self.create_virtual_output(sink_name.clone(), None, None)?;
+
+        let source_id = self.load_tracked_module(
+            "module-remap-source",
+            &[
+                &format!("master={}.monitor", sink_name),
+                &format!("source_name={}", name),
+                &description_arg("source_properties", &name)?,
+            ],
+        );
+
+        if source_id.is_err() {
+            let _ = self.unload_tracked_module(sink_id);
+        }
+        source_id
+    }
+
+    /// Returns the monitor source of `sink` as reported by the server, checked against
+    /// the live source list.
+    #[napi]
+    pub fn get_monitor_source(&self, sink: String) -> Result<String, BeacnError> {
+        self.ensure_initialized()?;
+
+        let block = find_sink(&sink)?;
+
+        let monitor = block
+            .field("Monitor Source")
+            .map(str::to_string)
+            .unwrap_or_else(|| format!("{}.monitor", sink));
+
+        if !self.device_exists(&monitor, false) {
+            return Err(BeacnError::NotFound(format!("monitor source '{}'", monitor)));
+        }
+
+        Ok(monitor)
+    }
+
+    /// Makes `sink` the default output. Under PipeWire this goes through
+    /// `wpctl set-default`, since WirePlumber reverts defaults set only through pactl;
+    /// pactl is still used if wpctl can't pick out the node.
+    #[napi]
+    pub fn set_default_sink(&self, sink: String) -> Result<(), BeacnError> {
+        self.set_default_device(&sink, true)
+    }
+
+    /// Makes `source` the default input, the same way as `set_default_sink`.
+    #[napi]
+    pub fn set_default_source(&self, source: String) -> Result<(), BeacnError> {
+        self.set_default_device(&source, false)
+    }
+
+    #[napi]
+    pub fn get_default_sink(&self) -> Result<String, BeacnError> {
+        self.get_default_device("Default Sink")
+    }
+
+    #[napi]
+    pub fn get_default_source(&self) -> Result<String, BeacnError> {
+        self.get_default_device("Default Source")
+    }
+
+    #[napi]
+    pub fn set_sink_volume(&self, sink: String, volume_percent: u32) -> Result<(), BeacnError> {
+        self.ensure_initialized()?;
+
+        validate_volume(volume_percent)?;
+        run_pactl(&["set-sink-volume", &sink, &format!("{}%", volume_percent)])?;
+        Ok(())
+    }
+
+    /// Reads the sink's current level. For multi-channel sinks `volume_percent` is the
+    /// rounded average across channels, so an unbalanced stereo pair reports its midpoint.
+    #[napi]
+    pub fn get_sink_volume(&self, sink: String) -> Result<SinkVolume, BeacnError> {
+        self.ensure_initialized()?;
+
+        let block = find_sink(&sink)?;
+
+        let percents = parse_channel_percents(block.field("Volume").unwrap_or(""));
+
+        Ok(SinkVolume {
+            volume_percent: average_percent(&percents),
+            muted: parse_yes_no(block.field("Mute")),
+            channels: percents.len() as u32,
+        })
+    }
+
+    /// Moves the sink's level by `delta_percent`, clamped to 0 and the maximum volume,
+    /// and returns the new level. Each channel moves by the same amount, so balance is
+    /// kept until a channel hits a limit.
+    #[napi]
+    pub fn step_sink_volume(&self, sink: String, delta_percent: i32) -> Result<u32, BeacnError> {
+        self.ensure_initialized()?;
+
+        let block = find_sink(&sink)?;
+        let percents: Vec<u32> = parse_channel_percents(block.field("Volume").unwrap_or(""))
+            .into_iter()
+            .map(|p| (p as i64 + delta_percent as i64).clamp(0, MAX_VOLUME_PERCENT as i64) as u32)
+            .collect();
+        if percents.is_empty() {
+            return Err(BeacnError::NotFound(format!("volume of sink '{}'", sink)));
+        }
+
+        let levels: Vec<String> = percents.iter().map(|p| format!("{}%", p)).collect();
+        let mut args = vec!["set-sink-volume", sink.as_str()];
+        args.extend(levels.iter().map(String::as_str));
+        run_pactl(&args)?;
+        Ok(average_percent(&percents))
+    }
+
+    /// The sink's current latency in microseconds, as the server reports it.
+    #[napi]
+    pub fn get_sink_latency(&self, sink: String) -> Result<i64, BeacnError> {
+        self.ensure_initialized()?;
+        device_latency(&find_sink(&sink)?, &sink)
+    }
+
+    /// Latency and buffer fill of `sink` and every stream playing into it, loopback
+    /// routes included, for telling whether a route's latency is set too low.
+    #[napi]
+    pub fn get_stream_stats(&self, sink: String) -> Result<StreamStats, BeacnError> {
+        self.ensure_initialized()?;
+
+        let block = find_sink(&sink)?;
+        let latency = block.field("Latency").unwrap_or("");
+        let index = block.index.to_string();
+        let usec = |field: Option<&str>| field.and_then(parse_latency_usec).map(|usec| usec as i64);
+        let loopbacks: Vec<u32> = list_modules()?
+            .iter()
+            .filter(|m| m.name() == Some("module-loopback"))
+            .map(|m| m.index)
+            .collect();
+
+        let streams = parse_blocks(&run_pactl(&["list", "sink-inputs"])?, "Sink Input")
+            .iter()
+            .filter(|b| b.field("Sink") == Some(index.as_str()))
+            .map(|b| StreamBufferStats {
+                input_id: b.index,
+                app_name: b.property("application.name").map(str::to_string),
+                route_module_id: b
+                    .field("Owner Module")
+                    .and_then(|owner| owner.parse().ok())
+                    .filter(|owner| loopbacks.contains(owner)),
+                buffer_latency_usec: usec(b.field("Buffer Latency")),
+                sink_latency_usec: usec(b.field("Sink Latency")),
+            })
+            .collect();
+
+        Ok(StreamStats {
+            state: block.field("State").unwrap_or("").to_string(),
+            latency_usec: device_latency(&block, &sink)?,
+            configured_latency_usec: parse_configured_latency_usec(latency).map(|usec| usec as i64),
+            streams,
+            sink,
+        })
+    }
+
+    /// The source's current latency in microseconds, as the server reports it.
+    #[napi]
+    pub fn get_source_latency(&self, source: String) -> Result<i64, BeacnError> {
+        self.ensure_initialized()?;
+        device_latency(&find_source(&source)?, &source)
+    }
+
+    /// Ramps the sink from its current level to `target_percent` over `duration_ms` on a
+    /// background thread. Starting another fade on the same sink cancels this one.
+    /// `callback`, if given, gets `true` once the target is reached or `false` if the
+    /// fade was cancelled or a step failed.
+    #[napi(ts_args_type = "sink: string, targetPercent: number, durationMs: number, callback?: (completed: boolean) => void")]
+    pub fn fade_sink_volume(
+        &self,
+        sink: String,
+        target_percent: u32,
+        duration_ms: u32,
+        callback: Option<JsFunction>,
+    ) -> Result<(), BeacnError> {
+        validate_volume(target_percent)?;
+
+        // Cancel first so the starting level isn't read mid-fade
+        let mut fades = self.fades.lock();
+        fades.remove(&sink);
+
+        let start = self.get_sink_volume(sink.clone())?.volume_percent as f64;
+        let on_done: Option<ThreadsafeFunction<bool, ErrorStrategy::Fatal>> = callback
+            .map(|cb| cb.create_threadsafe_function(0, |ctx: ThreadSafeCallContext<bool>| Ok(vec![ctx.value])))
+            .transpose()
+            .map_err(|e| BeacnError::InvalidArgument(e.reason))?;
+
+        let steps = (duration_ms / FADE_STEP_MS).max(1);
+        let target = sink.clone();
+        fades.insert(sink, Worker::spawn(move |stop| {
+            let mut completed = true;
+            for step in 1..=steps {
+                if stop.load(Ordering::Relaxed) {
+                    completed = false;
+                    break;
+                }
+
+                let level = start + (target_percent as f64 - start) * step as f64 / steps as f64;
+                if run_pactl(&["set-sink-volume", &target, &format!("{}%", level.round() as u32)]).is_err() {
+                    completed = false;
+                    break;
+                }
+
+                if step < steps {
+                    std::thread::sleep(std::time::Duration::from_millis(FADE_STEP_MS as u64));
+                }
+            }
+
+            if let Some(tsfn) = on_done {
+                tsfn.call(completed, ThreadsafeFunctionCallMode::NonBlocking);
+            }
+        }));
+
+        Ok(())
+    }
+
+    /// Pans a stereo sink: -1.0 is full left, 0.0 centred, 1.0 full right. The louder
+    /// channel keeps the sink's current level and the other is attenuated, the same
+    /// way pavucontrol's balance slider behaves.
+    #[napi]
+    pub fn set_sink_balance(&self, sink: String, balance: f64) -> Result<(), BeacnError> {
+        self.ensure_initialized()?;
+
+        if !(-1.0..=1.0).contains(&balance) {
+            return Err(BeacnError::InvalidArgument(format!("balance {} is outside -1.0 to 1.0", balance)));
+        }
+
+        let block = find_sink(&sink)?;
+        let percents = parse_channel_percents(block.field("Volume").unwrap_or(""));
+        if percents.len() != 2 {
+            return Err(BeacnError::Unsupported(format!(
+                "balance on '{}', which has {} channels instead of 2",
+                sink,
+                percents.len()
+            )));
+        }
+
+        let level = percents[0].max(percents[1]) as f64;
+        let left = (level * (1.0 - balance.max(0.0))).round() as u32;
+        let right = (level * (1.0 + balance.min(0.0))).round() as u32;
+
+        run_pactl(&["set-sink-volume", &sink, &format!("{}%", left), &format!("{}%", right)])?;
+        Ok(())
+    }
+
+    /// Sets each channel of the sink separately, in the sink's channel map order, e.g. to
+    /// trim the LFE of a surround sink. Needs exactly one percentage per channel.
+    #[napi]
+    pub fn set_sink_channel_volume(&self, sink: String, channel_volumes: Vec<u32>) -> Result<(), BeacnError> {
+        self.ensure_initialized()?;
+
+        for &percent in &channel_volumes {
+            validate_volume(percent)?;
+        }
+
+        let block = find_sink(&sink)?;
+        let channels = parse_channel_percents(block.field("Volume").unwrap_or("")).len();
+        if channel_volumes.len() != channels {
+            return Err(BeacnError::InvalidArgument(format!(
+                "{} channel volumes for '{}', which has {} channels",
+                channel_volumes.len(),
+                sink,
+                channels
+            )));
+        }
+
+        let levels: Vec<String> = channel_volumes.iter().map(|p| format!("{}%", p)).collect();
+        let mut args = vec!["set-sink-volume", sink.as_str()];
+        args.extend(levels.iter().map(String::as_str));
+        run_pactl(&args)?;
+        Ok(())
+    }
+
+    #[napi]
+    pub fn list_cards(&self) -> Vec<CardInfo> {
+        let output = match run_pactl(&["list", "cards"]) {
+            Ok(o) => o,
+            Err(_) => return Vec::new(),
+        };
+
+        parse_blocks(&output, "Card")
+            .iter()
+            .map(|b| CardInfo {
+                index: b.index,
+                name: b.name().unwrap_or("").to_string(),
+                active_profile: b.field("Active Profile").map(str::to_string),
+                profiles: b
+                    .sections
+                    .get("Profiles")
+                    .into_iter()
+                    .flatten()
+                    .filter_map(|line| parse_section_entry(line))
+                    .map(|(name, description, details)| CardProfile {
+                        name: name.to_string(),
+                        description: description.to_string(),
+                        // Older servers don't report availability at all
+                        available: !details.contains(&"available: no"),
+                    })
+                    .collect(),
+            })
+            .collect()
+    }
+
+    /// Switches `card` to `profile`, e.g. `output:analog-stereo` or `pro-audio`.
+    #[napi]
+    pub fn set_card_profile(&self, card: String, profile: String) -> Result<(), BeacnError> {
+        self.ensure_initialized()?;
+
+        let info = self
+            .list_cards()
+            .into_iter()
+            .find(|c| c.name == card)
+            .ok_or_else(|| BeacnError::NotFound(format!("card '{}'", card)))?;
+
+        if !info.profiles.iter().any(|p| p.name == profile) {
+            return Err(BeacnError::NotFound(format!("profile '{}' on card '{}'", profile, card)));
+        }
+
+        run_pactl(&["set-card-profile", &card, &profile])?;
+        Ok(())
+    }
+
+    /// Shifts the latency reported for `port` on `card` by `offset_usec`, positive or
+    /// negative, so players delay or advance audio to line up with video.
+    #[napi]
+    pub fn set_port_latency_offset(&self, card: String, port: String, offset_usec: i64) -> Result<(), BeacnError> {
+        self.ensure_initialized()?;
+
+        if !(-MAX_LATENCY_OFFSET_USEC..=MAX_LATENCY_OFFSET_USEC).contains(&offset_usec) {
+            return Err(BeacnError::InvalidArgument(format!(
+                "latency offset {}us is outside ±{}us",
+                offset_usec, MAX_LATENCY_OFFSET_USEC
+            )));
+        }
+
+        if !self.list_cards().iter().any(|c| c.name == card) {
+            return Err(BeacnError::NotFound(format!("card '{}'", card)));
+        }
+
+        // `--` keeps pactl from reading a negative offset as an option
+        run_pactl(&["--", "set-port-latency-offset", &card, &port, &offset_usec.to_string()])?;
+        Ok(())
+    }
+
+    /// Ports of the sink or source called `device`; empty if there is no such device.
+    #[napi]
+    pub fn list_ports(&self, device: String) -> Vec<PortInfo> {
+        find_sink(&device).or_else(|_| find_source(&device)).map(|b| device_ports(&b)).unwrap_or_default()
+    }
+
+    #[napi]
+    pub fn set_sink_port(&self, sink: String, port: String) -> Result<(), BeacnError> {
+        self.ensure_initialized()?;
+
+        if !device_ports(&find_sink(&sink)?).iter().any(|p| p.name == port) {
+            return Err(BeacnError::NotFound(format!("port '{}' on sink '{}'", port, sink)));
+        }
+
+        run_pactl(&["set-sink-port", &sink, &port])?;
+        Ok(())
+    }
+
+    #[napi]
+    pub fn set_source_port(&self, source: String, port: String) -> Result<(), BeacnError> {
+        self.ensure_initialized()?;
+
+        if !device_ports(&find_source(&source)?).iter().any(|p| p.name == port) {
+            return Err(BeacnError::NotFound(format!("port '{}' on source '{}'", port, source)));
+        }
+
+        run_pactl(&["set-source-port", &source, &port])?;
+        Ok(())
+    }
+
+    /// Suspends or resumes `sink` through `pactl suspend-sink`.
+    #[napi]
+    pub fn set_sink_suspended(&self, sink: String, suspended: bool) -> Result<(), BeacnError> {
+        self.ensure_initialized()?;
+
+        if !self.device_exists(&sink, true) {
+            return Err(BeacnError::NotFound(format!("sink '{}'", sink)));
+        }
+
+        run_pactl(&["suspend-sink", &sink, if suspended { "1" } else { "0" }])?;
+        Ok(())
+    }
+
+    #[napi]
+    pub fn set_sink_mute(&self, sink: String, muted: bool) -> Result<(), BeacnError> {
+        self.ensure_initialized()?;
+        run_pactl(&["set-sink-mute", &sink, if muted { "1" } else { "0" }])?;
+        Ok(())
+    }
+
+    #[napi]
+    pub fn set_source_mute(&self, source: String, muted: bool) -> Result<(), BeacnError> {
+        self.ensure_initialized()?;
+        run_pactl(&["set-source-mute", &source, if muted { "1" } else { "0" }])?;
+        Ok(())
+    }
+
+    /// Mutes or unmutes every sink this instance created, leaving other sinks alone.
+    #[napi]
+    pub fn mute_all(&self, muted: bool) -> Result<SinkBatchReport, BeacnError> {
+        self.ensure_initialized()?;
+        Ok(self.for_each_tracked_sink(|sink| self.set_sink_mute(sink, muted)))
+    }
+
+    /// Sets every sink this instance created to `percent`, leaving other sinks alone.
+    #[napi]
+    pub fn set_volume_all(&self, percent: u32) -> Result<SinkBatchReport, BeacnError> {
+        self.ensure_initialized()?;
+        validate_volume(percent)?;
+        Ok(self.for_each_tracked_sink(|sink| self.set_sink_volume(sink, percent)))
+    }
+
+    /// Flips the sink's mute state and returns the new value.
+    #[napi]
+    pub fn toggle_sink_mute(&self, sink: String) -> Result<bool, BeacnError> {
+        let muted = !self.get_sink_volume(sink.clone())?.muted;
+        self.set_sink_mute(sink, muted)?;
+        Ok(muted)
+    }
+
+    /// Connects `source` to `destination`. On classic PulseAudio this loads a
+    /// `module-loopback` and returns its module ID. Under PipeWire plain routes link
+    /// the two nodes' ports with `pw-link` instead and return `None`; asking for a
+    /// latency or any loopback option forces a loopback. Either kind is tracked, so
+    /// `list_routes`, `remove_route`, `save_config` and cleanup see it.
+    #[napi]
+    pub fn route_audio(
+        &self,
+        source: String,
+        destination: String,
+        latency_msec: Option<u32>,
+        options: Option<RouteOptions>,
+    ) -> Result<Option<u32>, BeacnError> {
+        self.ensure_initialized()?;
+        let options = options.unwrap_or_default();
+
+        if *self.backend.lock() == Backend::PipeWire && latency_msec.is_none() && !options.needs_loopback() {
+            return self.link_route(source, destination).map(|_| None);
+        }
+
+        let mut args = vec![
+            format!("source={}", source),
+            format!("sink={}", destination),
+            format!("source_output_properties=\"{}\"", ROUTE_STREAM_PROPERTIES),
+            format!("sink_input_properties=\"{}\"", ROUTE_STREAM_PROPERTIES),
+        ];
+        if let Some(latency) = latency_msec {
+            if !LOOPBACK_LATENCY_RANGE_MS.contains(&latency) {
+                return Err(BeacnError::InvalidArgument(format!(
+                    "latency {}ms is outside {}-{}ms",
+                    latency,
+                    LOOPBACK_LATENCY_RANGE_MS.start(),
+                    LOOPBACK_LATENCY_RANGE_MS.end()
+                )));
+            }
+            args.push(format!("latency_msec={}", latency));
+        }
+        if options.source_dont_move == Some(true) {
+            args.push("source_dont_move=true".to_string());
+        }
+        if options.sink_dont_move == Some(true) {
+            args.push("sink_dont_move=true".to_string());
+        }
+        if options.remix == Some(false) {
+            args.push("remix=no".to_string());
+        }
+
+        let settle_ms = options.settle_ms.unwrap_or(DEFAULT_ROUTE_SETTLE_MS);
+        if settle_ms > MAX_ROUTE_SETTLE_MS {
+            return Err(BeacnError::InvalidArgument(format!(
+                "settle delay {}ms exceeds the maximum of {}ms",
+                settle_ms, MAX_ROUTE_SETTLE_MS
+            )));
+        }
+
+        let args: Vec<&str> = args.iter().map(String::as_str).collect();
+        let module_id = self.load_tracked_module("module-loopback", &args)?;
+
+        // The route itself is up either way, so a failed mute is only worth a warning
+        if options.start_muted == Some(true) {
+            let muted = route_sink_input(module_id)
+                .and_then(|input| run_pactl(&["set-sink-input-mute", &input.to_string(), "1"]).map(|_| input));
+            match muted {
+                Ok(input) => {
+                    let unmute = Worker::spawn(move |stop| {
+                        let mut remaining = settle_ms as u64;
+                        while remaining > 0 && !stop.load(Ordering::Relaxed) {
+                            let step = remaining.min(WATCH_POLL_MS);
+                            std::thread::sleep(std::time::Duration::from_millis(step));
+                            remaining -= step;
+                        }
+                        if let Err(e) = run_pactl(&["set-sink-input-mute", &input.to_string(), "0"]) {
+                            logging::warn(format_args!("Could not unmute route {}: {}", module_id, e));
+                        }
+                    });
+                    self.route_unmutes.lock().insert(module_id, unmute);
+                }
+                Err(e) => logging::warn(format_args!("Could not mute route {} while it settles: {}", module_id, e)),
+            }
+        }
+
+        Ok(Some(module_id))
+    }
+
+    /// Every port-to-port link in the PipeWire graph, including the ones loopbacks and
+    /// other apps make. Empty on classic PulseAudio or if `pw-link` fails.
+    #[napi]
+    pub fn get_pipewire_links(&self) -> Vec<PwLink> {
+        if self.ensure_pipewire().is_err() {
+            return Vec::new();
+        }
+
+        match run_command("pw-link", &["-l"]) {
+            Ok(output) => parse_pw_links(&output),
+            Err(e) => {
+                logging::warn(format_args!("Could not list PipeWire links: {}", e));
+                Vec::new()
+            }
+        }
+    }
+
+    /// Links every matching port of two PipeWire nodes with `pw-link`.
+    #[napi]
+    pub fn link_nodes(&self, source_node: String, sink_node: String) -> Result<(), BeacnError> {
+        self.ensure_pipewire()?;
+        run_command("pw-link", &[&source_node, &sink_node])?;
+        Ok(())
+    }
+
+    #[napi]
+    pub fn unlink_nodes(&self, source_node: String, sink_node: String) -> Result<(), BeacnError> {
+        self.ensure_pipewire()?;
+        run_command("pw-link", &["--disconnect", &source_node, &sink_node])?;
+        Ok(())
+    }
+
+    #[napi]
+    pub fn list_sink_inputs(&self) -> Vec<SinkInput> {
+        self.list_streams("sink-inputs", "Sink Input", "Sink", true)
+            .into_iter()
+            .map(|s| SinkInput {
+                id: s.id,
+                app_name: s.app_name,
+                media_name: s.media_name,
+                current_sink: s.device,
+                volume_percent: s.volume_percent,
+                muted: s.muted,
+            })
+            .collect()
+    }
+
+    #[napi]
+    pub fn list_source_outputs(&self) -> Vec<SourceOutput> {
+        self.list_streams("source-outputs", "Source Output", "Source", false)
+            .into_iter()
+            .map(|s| SourceOutput {
+                id: s.id,
+                app_name: s.app_name,
+                media_name: s.media_name,
+                current_source: s.device,
+                volume_percent: s.volume_percent,
+                muted: s.muted,
+            })
+            .collect()
+    }
+
+    #[napi]
+    pub fn set_sink_input_volume(&self, input_id: u32, volume_percent: u32) -> Result<(), BeacnError> {
+        self.ensure_initialized()?;
+        validate_volume(volume_percent)?;
+        run_pactl(&["set-sink-input-volume", &input_id.to_string(), &format!("{}%", volume_percent)])?;
+        Ok(())
+    }
+
+    /// Makes new streams from the application named `app_name` (its `application.name`,
+    /// as in `list_sink_inputs`) start on `sink`, across app and server restarts. Streams
+    /// already playing stay where they are; use `move_sink_input` for those.
+    #[napi]
+    pub fn set_app_default_sink(&self, app_name: String, sink: String) -> Result<(), BeacnError> {
+        self.ensure_initialized()?;
+
+        if app_name.trim().is_empty() {
+            return Err(BeacnError::InvalidArgument("application name is empty".to_string()));
+        }
+        if !self.device_exists(&sink, true) {
+            return Err(BeacnError::NotFound(format!("sink '{}'", sink)));
+        }
+
+        let rule = format!("sink-input-by-application-name:{}", app_name);
+        self.with_connection(|mainloop, context| write_stream_device(mainloop, context, &rule, &sink))
+    }
+
+    /// Moves a playing stream to `sink` without interrupting it.
+    #[napi]
+    pub fn move_sink_input(&self, input_id: u32, sink: String) -> Result<(), BeacnError> {
+        self.ensure_initialized()?;
+
+        if !self.device_exists(&sink, true) {
+            return Err(BeacnError::NotFound(format!("sink '{}'", sink)));
+        }
+
+        run_pactl(&["move-sink-input", &input_id.to_string(), &sink])?;
+        Ok(())
+    }
+
+    /// Moves every stream playing to `from_sink` over to `to_sink` and returns how many
+    /// were moved. Run it before `remove_virtual_output` so apps keep playing.
+    #[napi]
+    pub fn move_all_sink_inputs(&self, from_sink: String, to_sink: String) -> Result<u32, BeacnError> {
+        self.ensure_initialized()?;
+
+        for sink in [&from_sink, &to_sink] {
+            if !self.device_exists(sink, true) {
+                return Err(BeacnError::NotFound(format!("sink '{}'", sink)));
+            }
+        }
+
+        let inputs: Vec<u32> = self
+            .list_sink_inputs()
+            .into_iter()
+            .filter(|i| i.current_sink.as_deref() == Some(from_sink.as_str()))
+            .map(|i| i.id)
+            .collect();
+
+        for &id in &inputs {
+            run_pactl(&["move-sink-input", &id.to_string(), &to_sink])?;
+        }
+
+        Ok(inputs.len() as u32)
+    }
+
+    /// Every sink and source as a node and every loopback as an edge, for patchbay UIs.
+    #[napi]
+    pub fn get_routing_table(&self) -> RoutingTable {
+        let nodes = self
+            .get_audio_devices()
+            .into_iter()
+            .map(|d| Node { index: d.index, name: d.name, kind: d.kind })
+            .collect();
+
+        // A loopback's gain lives on the sink input it owns
+        let inputs = run_pactl(&["list", "sink-inputs"]).map(|o| parse_blocks(&o, "Sink Input")).unwrap_or_default();
+        let route_volume = |module_id: Option<u32>| {
+            let Some(owner) = module_id.map(|id| id.to_string()) else { return 100 };
+            inputs
+                .iter()
+                .find(|b| b.field("Owner Module") == Some(owner.as_str()))
+                .map_or(100, |b| average_percent(&parse_channel_percents(b.field("Volume").unwrap_or(""))))
+        };
+
+        let edges = self
+            .list_routes()
+            .into_iter()
+            .filter_map(|r| {
+                Some(Edge {
+                    module_id: r.module_id,
+                    volume_percent: route_volume(r.module_id),
+                    from: r.source?,
+                    to: r.sink?,
+                    latency_msec: r.latency_msec,
+                })
+            })
+            .collect();
+
+        RoutingTable { nodes, edges }
+    }
+
+    /// Renders devices and loopback routes as a Graphviz digraph (`dot -Tpng`). Sinks
+    /// are boxes, sources ellipses and monitors dashed ellipses, labelled with their
+    /// volume and mute state.
+    #[napi]
+    pub fn export_graph_dot(&self) -> Result<String, BeacnError> {
+        self.ensure_initialized()?;
+        Ok(routing_dot(&self.get_audio_devices_detailed(), &self.list_routes()))
+    }
+
+    /// Sets the gain of one loopback route without touching its sink's volume. The route's
+    /// sink input (the stream the loopback plays into the sink) carries the level, so
+    /// each route works as its own fader.
+    #[napi]
+    pub fn set_route_volume(&self, route_module_id: u32, volume_percent: u32) -> Result<(), BeacnError> {
+        self.ensure_initialized()?;
+        validate_volume(volume_percent)?;
+
+        self.set_sink_input_volume(route_sink_input(route_module_id)?, volume_percent)
+    }
+
+    /// Lists every loaded `module-loopback`, including ones created outside BEACN Link,
+    /// followed by this instance's `pw-link` routes. Routes whose ends run at
+    /// different rates are flagged as `resampling`.
+    #[napi]
+    pub fn list_routes(&self) -> Vec<RouteInfo> {
+        let modules = list_modules().unwrap_or_default();
+
+        let devices = self.get_audio_devices();
+        let rate_of = |name: Option<&str>, is_output: bool| {
+            devices.iter().find(|d| d.is_output() == is_output && Some(d.name.as_str()) == name).map(|d| d.sample_rate)
+        };
+
+        modules
+            .iter()
+            .filter(|b| b.name() == Some("module-loopback"))
+            .map(|b| {
+                let args = b.module_args();
+                let source = module_arg(&args, "source");
+                let sink = module_arg(&args, "sink");
+                RouteInfo {
+                    module_id: Some(b.index),
+                    source: source.map(str::to_string),
+                    sink: sink.map(str::to_string),
+                    latency_msec: module_arg(&args, "latency_msec").and_then(|l| l.parse().ok()),
+                    resampling: matches!(
+                        (rate_of(source, false), rate_of(sink, true)),
+                        (Some(from), Some(to)) if from != to
+                    ),
+                    // A loopback without an endpoint argument follows the default device
+                    valid: (source.is_none() || rate_of(source, false).is_some())
+                        && (sink.is_none() || rate_of(sink, true).is_some()),
+                    remix: !module_arg(&args, "remix").is_some_and(|r| matches!(r, "0" | "no" | "false" | "off")),
+                }
+            })
+            .chain(self.own_links.lock().iter().map(|link| {
+                let (source, sink) = (Some(link.source.as_str()), Some(link.sink.as_str()));
+                RouteInfo {
+                    module_id: None,
+                    source: source.map(str::to_string),
+                    sink: sink.map(str::to_string),
+                    latency_msec: None,
+                    resampling: matches!(
+                        (rate_of(source, false), rate_of(sink, true)),
+                        (Some(from), Some(to)) if from != to
+                    ),
+                    valid: rate_of(source, false).is_some() && rate_of(sink, true).is_some(),
+                    remix: false,
+                }
+            }))
+            .collect()
+    }
+
+    /// Removes every route between `source` and `sink`, loopback or `pw-link`, and
+    /// returns how many were removed.
+    #[napi]
+    pub fn remove_route(&self, source: String, sink: String) -> Result<u32, BeacnError> {
+        self.ensure_initialized()?;
+
+        let matching: Vec<RouteInfo> = self
+            .list_routes()
+            .into_iter()
+            .filter(|r| r.source.as_deref() == Some(source.as_str()) && r.sink.as_deref() == Some(sink.as_str()))
+            .collect();
+
+        if matching.is_empty() {
+            return Err(BeacnError::NotFound(format!("route {} -> {}", source, sink)));
+        }
+
+        for route in &matching {
+            self.remove_listed_route(route)?;
+        }
+
+        Ok(matching.len() as u32)
+    }
+
+    /// Loads any PulseAudio module, e.g. `load_module("module-echo-cancel", [...])`, for
+    /// the ones BEACN Link doesn't wrap. Each entry of `args` is one `key=value`
+    /// argument. The module is tracked, so it is unloaded with the others.
+    #[napi]
+    pub fn load_module(&self, module_name: String, args: Vec<String>) -> Result<u32, BeacnError> {
+        self.ensure_initialized()?;
+
+        let valid_name = module_name.starts_with("module-")
+            && module_name.chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || matches!(c, '-' | '_'));
+        if !valid_name {
+            return Err(BeacnError::InvalidArgument(format!("{:?} is not a module name", module_name)));
+        }
+
+        let args: Vec<&str> = args.iter().map(String::as_str).collect();
+        self.load_tracked_module(&module_name, &args)
+    }
+
+    /// Unloads every loopback whose source or sink no longer exists (see
+    /// `RouteInfo.valid`), including ones BEACN Link didn't create, and returns how
+    /// many were removed.
+    #[napi]
+    pub fn prune_invalid_routes(&self) -> Result<u32, BeacnError> {
+        self.ensure_initialized()?;
+
+        let dead: Vec<RouteInfo> = self.list_routes().into_iter().filter(|r| !r.valid).collect();
+        for route in &dead {
+            self.remove_listed_route(route)?;
+        }
+
+        Ok(dead.len() as u32)
+    }
+
+    /// Unloads every loopback route this instance created, newest first, then its
+    /// `pw-link` routes, and returns how many were removed. Virtual devices and other software's loopbacks stay put.
+    #[napi]
+    pub fn unload_all_loopbacks(&self) -> Result<u32, BeacnError> {
+        self.ensure_initialized()?;
+
+        let loopbacks: Vec<u32> = self
+            .own_modules
+            .lock()
+            .iter()
+            .rev()
+            .filter(|m| m.module == "module-loopback")
+            .map(|m| m.id)
+            .collect();
+
+        for &id in &loopbacks {
+            self.unload_tracked_module(id)?;
+        }
+
+        let links = std::mem::take(&mut *self.own_links.lock());
+        unlink_routes(&links)?;
+
+        Ok((loopbacks.len() + links.len()) as u32)
+    }
+
+    #[napi]
+    pub fn unload_module(&self, module_id: u32) -> Result<(), BeacnError> {
+        self.ensure_initialized()?;
+
+        let id = module_id.to_string();
+        let modules = run_pactl(&["list", "short", "modules"])?;
+        if !modules.lines().any(|line| line.split('\t').next() == Some(id.as_str())) {
+            return Err(BeacnError::NotFound(format!("module {}", module_id)));
+        }
+
+        self.unload_tracked_module(module_id)
+    }
+
+    /// The raw `Argument:` string module `module_id` was loaded with, e.g. to rebuild a
+    /// device created outside BEACN Link. Empty for modules loaded without arguments.
+    #[napi]
+    pub fn get_module_arguments(&self, module_id: u32) -> Result<String, BeacnError> {
+        self.ensure_initialized()?;
+
+        list_modules()?
+            .into_iter()
+            .find(|b| b.index == module_id)
+            .map(|b| b.field("Argument").unwrap_or("").to_string())
+            .ok_or_else(|| BeacnError::NotFound(format!("module {}", module_id)))
+    }
+
+    /// Module ID of the `module-null-sink` loaded with `sink_name=<sink_name>`, whoever
+    /// loaded it. Lets callers skip creating a device that survived an app restart.
+    #[napi]
+    pub fn get_module_id(&self, sink_name: String) -> Option<u32> {
+        find_null_sink_module(&sink_name).ok().flatten()
+    }
+
+    /// Whether any module of type `module_name` (e.g. `module-loopback`) is loaded.
+    #[napi]
+    pub fn is_module_loaded(&self, module_name: String) -> bool {
+        list_modules().is_ok_and(|modules| modules.iter().any(|b| b.name() == Some(module_name.as_str())))
+    }
+
+    /// Unloads the null sink that was created with exactly `sink_name=<name>`.
+    #[napi]
+    pub fn remove_virtual_output(&self, name: String) -> Result<(), BeacnError> {
+        self.ensure_initialized()?;
+
+        let module_id = find_null_sink_module(&name)?
+            .ok_or_else(|| BeacnError::NotFound(format!("virtual output '{}'", name)))?;
+
+        self.unload_tracked_module(module_id)
+    }
+
+    /// Unloads every module this instance loaded, newest first, leaving other
+    /// applications' devices alone.
+    #[napi]
+    pub fn cleanup_own_modules(&self) -> Result<(), BeacnError> {
+        self.ensure_initialized()?;
+        self.unload_own_modules()
+    }
+
+    /// Creates `count` outputs named `<prefix>_Out`, `<prefix>_2_Out`, ... (prefix
+    /// defaults to `BEACN_Link`) and returns their module IDs in order. Outputs that
+    /// already exist are reused, so repeated calls are safe, but a sink of the same
+    /// name that isn't a null sink is an error. If one fails, the outputs created by
+    /// this call are unloaded again. `count` must be 1-32.
+    #[napi]
+    pub fn create_link_outputs(&self, count: u32, prefix: Option<String>) -> Result<Vec<u32>, BeacnError> {
+        self.ensure_initialized()?;
+        validate_link_output_count(count)?;
+
+        let prefix = prefix.unwrap_or_else(|| DEFAULT_LINK_PREFIX.to_string());
+        validate_sink_name(&prefix)?;
+
+        let existing_sinks: Vec<String> = self
+            .get_audio_devices()
+            .into_iter()
+            .filter(|d| d.is_output())
+            .map(|d| d.name)
+            .collect();
+
+        let names = link_output_names(&prefix, count);
+        names.iter().try_for_each(|name| validate_sink_name(name))?;
+
+        let mut module_ids = Vec::new();
+        let mut created_ids = Vec::new();
+
+        for name in names {
+            let result = if existing_sinks.contains(&name) {
+                // A sink of that name from anything but a null sink can't stand in for it
+                find_null_sink_module(&name).and_then(|id| id.ok_or(BeacnError::DuplicateName(name)))
+            } else {
+                self.create_virtual_output(name, None, None).inspect(|&id| created_ids.push(id))
+            };
+
+            match result {
+                Ok(id) => module_ids.push(id),
+                Err(e) => {
+                    for id in created_ids.into_iter().rev() {
+                        let _ = self.unload_tracked_module(id);
+                    }
+                    return Err(e);
+                }
+            }
+        }
+
+        Ok(module_ids)
+    }
+
+    /// Writes the virtual outputs, virtual sources and loopback routes this instance
+    /// created to `path` as JSON, `pw-link` routes included. Anything made outside
+    /// BEACN Link, and links made directly with `link_nodes`, are left out.
+    #[napi]
+    pub fn save_config(&self, path: String) -> Result<(), BeacnError> {
+        self.ensure_initialized()?;
+        let modules = self.own_modules.lock();
+        let tracked: Vec<(&str, Vec<(String, String)>)> =
+            modules.iter().map(|m| (m.module.as_str(), parse_module_args(&m.args.join(" ")))).collect();
+
+        // The null sink behind a virtual source is recreated along with the source
+        let backing_sinks: Vec<String> = tracked
+            .iter()
+            .filter(|(module, _)| *module == "module-remap-source")
+            .filter_map(|(_, args)| module_arg(args, "master"))
+            .map(|master| master.trim_end_matches(".monitor").to_string())
+            .collect();
+
+        let mut config = LinkConfig { version: CONFIG_VERSION, ..Default::default() };
+        for (module, args) in &tracked {
+            match *module {
+                "module-null-sink" => {
+                    if let Some(name) = module_arg(args, "sink_name").filter(|n| !backing_sinks.iter().any(|b| b == n)) {
+                        config.outputs.push(OutputConfig { name: name.to_string(), format: saved_format(args) });
+                    }
+                }
+                "module-remap-source" => {
+                    if let Some(name) = module_arg(args, "source_name") {
+                        config.sources.push(SourceConfig { name: name.to_string() });
+                    }
+                }
+                "module-loopback" => {
+                    if let (Some(source), Some(sink)) = (module_arg(args, "source"), module_arg(args, "sink")) {
+                        config.routes.push(RouteConfig {
+                            source: source.to_string(),
+                            sink: sink.to_string(),
+                            latency_msec: module_arg(args, "latency_msec").and_then(|l| l.parse().ok()),
+                        });
+                    }
+                }
+                _ => {}
+            }
+        }
+        config.routes.extend(self.own_links.lock().iter().map(|link| RouteConfig {
+            source: link.source.clone(),
+            sink: link.sink.clone(),
+            latency_msec: None,
+        }));
+
+        write_config(&path, &config)
+    }
+
+    /// Recreates what `save_config` wrote to `path`. Devices that already exist and
+    /// routes already in place are skipped, as are routes whose source or sink is gone;
+    /// one failure doesn't stop the rest from being restored.
+    #[napi]
+    pub fn load_config(&self, path: String) -> Result<ConfigReport, BeacnError> {
+        self.ensure_initialized()?;
+        let config = read_config(&path)?;
+        let mut report = ConfigReport { created: Vec::new(), skipped: Vec::new(), failed: Vec::new() };
+
+        for output in config.outputs {
+            let item = format!("output {}", output.name);
+            if self.device_exists(&output.name, true) {
+                report.skipped.push(format!("{} (already exists)", item));
+                continue;
+            }
+            match self.create_virtual_output(output.name, output.format.map(SinkFormat::from), None) {
+                Ok(_) => report.created.push(item),
+                Err(e) => report.failed.push(format!("{}: {}", item, e)),
+            }
+        }
+
+        for source in config.sources {
+            let item = format!("source {}", source.name);
+            if self.device_exists(&source.name, false) {
+                report.skipped.push(format!("{} (already exists)", item));
+                continue;
+            }
+            match self.create_virtual_source(source.name) {
+                Ok(_) => report.created.push(item),
+                Err(e) => report.failed.push(format!("{}: {}", item, e)),
+            }
+        }
+
+        let existing_routes = self.list_routes();
+        for route in config.routes {
+            let item = format!("route {} -> {}", route.source, route.sink);
+            if !self.device_exists(&route.source, false) {
+                report.skipped.push(format!("{} (source '{}' no longer exists)", item, route.source));
+                continue;
+            }
+            if !self.device_exists(&route.sink, true) {
+                report.skipped.push(format!("{} (sink '{}' no longer exists)", item, route.sink));
+                continue;
+            }
+            let present = existing_routes.iter().any(|r| {
+                r.source.as_deref() == Some(route.source.as_str()) && r.sink.as_deref() == Some(route.sink.as_str())
+            });
+            if present {
+                report.skipped.push(format!("{} (already routed)", item));
+                continue;
+            }
+            match self.route_audio(route.source, route.sink, route.latency_msec, None) {
+                Ok(_) => report.created.push(item),
+                Err(e) => report.failed.push(format!("{}: {}", item, e)),
+            }
+        }
+
+        Ok(report)
+    }
+
+    /// One-step setup: creates the four BEACN Link outputs if missing, finds the sink
+    /// of the first BEACN device with an ALSA card, and routes each output's monitor to
+    /// it. Outputs and routes that already exist are left as they are.
+    #[napi]
+    pub fn auto_setup(&self) -> Result<SetupReport, BeacnError> {
+        self.ensure_initialized()?;
+        let mut report =
+            SetupReport { created_sinks: Vec::new(), created_routes: Vec::new(), hardware_sink: None, failed: Vec::new() };
+
+        let mut outputs = Vec::new();
+        for name in link_output_names(DEFAULT_LINK_PREFIX, DEFAULT_LINK_OUTPUT_COUNT) {
+            if self.device_exists(&name, true) {
+                outputs.push(name);
+                continue;
+            }
+            match self.create_virtual_output(name.clone(), None, None) {
+                Ok(_) => {
+                    report.created_sinks.push(name.clone());
+                    outputs.push(name);
+                }
+                Err(e) => report.failed.push(format!("create output {}: {}", name, e)),
+            }
+        }
+
+        let hardware_sink = match self.find_hardware_sink() {
+            Ok(sink) => sink,
+            Err(e) => {
+                report.failed.push(format!("find BEACN hardware sink: {}", e));
+                return Ok(report);
+            }
+        };
+
+        let existing_routes = self.list_routes();
+        for output in outputs {
+            let monitor = match self.get_monitor_source(output.clone()) {
+                Ok(m) => m,
+                Err(e) => {
+                    report.failed.push(format!("route {}: {}", output, e));
+                    continue;
+                }
+            };
+
+            let item = format!("{} -> {}", monitor, hardware_sink);
+            let present = existing_routes.iter().any(|r| {
+                r.source.as_deref() == Some(monitor.as_str()) && r.sink.as_deref() == Some(hardware_sink.as_str())
+            });
+            if present {
+                continue;
+            }
+            match self.route_audio(monitor, hardware_sink.clone(), None, None) {
+                Ok(_) => report.created_routes.push(item),
+                Err(e) => report.failed.push(format!("route {}: {}", item, e)),
+            }
+        }
+
+        report.hardware_sink = Some(hardware_sink);
+        Ok(report)
+    }
+}
+
+impl BeacnLink {
+    fn ensure_initialized(&self) -> Result<(), BeacnError> {
+        if self.pulse_context.lock().is_none() {
+            return Err(BeacnError::NotInitialized);
+        }
+        Ok(())
+    }
+
+    fn ensure_pipewire(&self) -> Result<(), BeacnError> {
+        self.ensure_initialized()?;
+        let backend = *self.backend.lock();
+        if backend != Backend::PipeWire {
+            return Err(BeacnError::Unsupported(format!("pw-link on the {} backend", backend.as_str())));
+        }
+        Ok(())
+    }
+
+    fn get_default_device(&self, key: &str) -> Result<String, BeacnError> {
+        self.ensure_initialized()?;
+
+        // Servers with nothing configured omit the line or print `n/a`
+        self.server_info()?
+            .remove(key)
+            .filter(|name| !name.is_empty() && name != "n/a")
+            .ok_or_else(|| BeacnError::NotFound(key.to_lowercase()))
+    }
+
+    fn list_streams(&self, kind: &str, header: &str, device_field: &str, is_output: bool) -> Vec<Stream> {
+        let output = match run_pactl(&["list", kind]) {
+            Ok(o) => o,
+            Err(_) => return Vec::new(),
+        };
+
+        // Streams only report their device's index, so resolve it against the device list
+        let devices: Vec<AudioDevice> =
+            self.get_audio_devices().into_iter().filter(|d| d.is_output() == is_output).collect();
+
+        parse_blocks(&output, header)
+            .iter()
+            .map(|b| Stream {
+                id: b.index,
+                app_name: b.property("application.name").map(str::to_string),
+                media_name: b.property("media.name").map(str::to_string),
+                device: b
+                    .field(device_field)
+                    .and_then(|index| devices.iter().find(|d| d.id == index))
+                    .map(|d| d.name.clone()),
+                volume_percent: average_percent(&parse_channel_percents(b.field("Volume").unwrap_or(""))),
+                muted: parse_yes_no(b.field("Mute")),
+            })
+            .collect()
+    }
+
+    /// Loads `module-pipe-sink` (`is_output`) or `module-pipe-source` on the FIFO `path`.
+    fn create_pipe(&self, module: &str, is_output: bool, name: String, path: String) -> Result<u32, BeacnError> {
+        self.ensure_initialized()?;
+        validate_sink_name(&name)?;
+
+        // The server opens the FIFO itself, from its own working directory
+        let fifo = std::path::Path::new(&path);
+        if !fifo.is_absolute() {
+            return Err(BeacnError::InvalidArgument(format!("pipe path '{}' is not absolute", path)));
+        }
+        if path.chars().any(|c| c.is_whitespace() || c.is_control() || matches!(c, '\'' | '"' | '\\')) {
+            return Err(BeacnError::InvalidArgument(format!(
+                "pipe path {:?} contains whitespace, quotes or backslashes",
+                path
+            )));
+        }
+        if !fifo.parent().is_some_and(|dir| dir.is_dir()) {
+            return Err(BeacnError::NotFound(format!("directory of pipe '{}'", path)));
+        }
+
+        if self.device_exists(&name, is_output) {
+            return Err(BeacnError::DuplicateName(name));
+        }
+
+        let (kind, properties) = if is_output { ("sink", "sink_properties") } else { ("source", "source_properties") };
+        self.load_tracked_module(
+            module,
+            &[&format!("file={}", path), &format!("{}_name={}", kind, name), &description_arg(properties, &name)?],
+        )
+    }
+
+    /// Loads `module-tunnel-sink` (`is_output`) or `module-tunnel-source` to `server`.
+    fn create_tunnel(&self, module: &str, is_output: bool, name: String, server: String) -> Result<u32, BeacnError> {
+        self.ensure_initialized()?;
+        validate_sink_name(&name)?;
+        validate_server_address(&server)?;
+
+        if self.device_exists(&name, is_output) {
+            return Err(BeacnError::DuplicateName(name));
+        }
+
+        let (kind, properties) = if is_output { ("sink", "sink_properties") } else { ("source", "source_properties") };
+        self.load_tracked_module(
+            module,
+            &[
+                &format!("server={}", server),
+                &format!("{}_name={}", kind, name),
+                &description_arg(properties, &name)?,
+            ],
+        )
+    }
+
+    /// Loads `module-remap-sink` (`is_output`) or `module-remap-source` over `master`.
+    #[allow(clippy::too_many_arguments)]
+    fn create_remap(
+        &self,
+        module: &str,
+        is_output: bool,
+        name: String,
+        master: String,
+        channels: u8,
+        channel_map: String,
+        master_channel_map: String,
+    ) -> Result<u32, BeacnError> {
+        self.ensure_initialized()?;
+        validate_sink_name(&name)?;
+
+        let (kind, properties) = if is_output { ("sink", "sink_properties") } else { ("source", "source_properties") };
+
+        if self.device_exists(&name, is_output) {
+            return Err(BeacnError::DuplicateName(name));
+        }
+
+        if !self.device_exists(&master, is_output) {
+            return Err(BeacnError::NotFound(format!("master {} '{}'", kind, master)));
+        }
+
+        if !(1..=MAX_SINK_CHANNELS).contains(&channels) {
+            return Err(BeacnError::InvalidArgument(format!("{} channels is outside 1-{}", channels, MAX_SINK_CHANNELS)));
+        }
+
+        self.load_tracked_module(
+            module,
+            &[
+                &format!("{}_name={}", kind, name),
+                &format!("master={}", master),
+                &format!("channels={}", channels),
+                &format!("channel_map={}", channel_map_arg(&channel_map, channels)?),
+                &format!("master_channel_map={}", channel_map_arg(&master_channel_map, channels)?),
+                // The maps say exactly which channel goes where, so don't let the server mix
+                "remix=no",
+                &description_arg(properties, &name)?,
+            ],
+        )
+    }
+
+    /// The sink on the ALSA card of the first detected BEACN device.
+    fn find_hardware_sink(&self) -> Result<String, BeacnError> {
+        let card = self
+            .detect_beacn_hardware()
+            .into_iter()
+            .find_map(|d| d.alsa_card)
+            .ok_or_else(|| BeacnError::NotFound("BEACN device with an ALSA card".to_string()))?
+            .to_string();
+
+        parse_blocks(&run_pactl(&["list", "sinks"])?, "Sink")
+            .iter()
+            .find(|b| b.property("alsa.card") == Some(card.as_str()))
+            .and_then(|b| b.name().map(str::to_string))
+            .ok_or_else(|| BeacnError::NotFound(format!("sink for ALSA card {}", card)))
+    }
+
+    fn connect_task(&self, server: Option<String>) -> ConnectTask {
+        ConnectTask {
+            server,
+            iterations: self.connect_iterations,
+            pulse_context: self.pulse_context.clone(),
+            pulse_mainloop: self.pulse_mainloop.clone(),
+            backend: self.backend.clone(),
+        }
+    }
+
+    /// Runs `f` against this instance's own connection.
+    fn with_connection<T>(
+        &self,
+        f: impl FnOnce(&mut Mainloop, &Context) -> Result<T, BeacnError>,
+    ) -> Result<T, BeacnError> {
+        let mut mainloop = self.pulse_mainloop.lock();
+        let context = self.pulse_context.lock();
+        match (mainloop.as_mut(), context.as_ref()) {
+            (Some(mainloop), Some(context)) => f(mainloop, context),
+            _ => Err(BeacnError::NotInitialized),
+        }
+    }
+
+    /// `pactl info` as key/value pairs, or the same keys from libpulse if `pactl`
+    /// isn't installed.
+    fn server_info(&self) -> Result<HashMap<String, String>, BeacnError> {
+        match run_pactl(&["info"]) {
+            Err(BeacnError::PactlNotFound) => self.with_connection(native_info),
+            info => info.map(|i| parse_info(&i)),
+        }
+    }
+
+    fn set_default_device(&self, name: &str, is_output: bool) -> Result<(), BeacnError> {
+        self.ensure_initialized()?;
+        let (kind, command) = if is_output { ("sink", "set-default-sink") } else { ("source", "set-default-source") };
+
+        // pactl happily accepts unknown names here, so check first
+        let device = self
+            .get_audio_devices()
+            .into_iter()
+            .find(|d| d.is_output() == is_output && d.name == name)
+            .ok_or_else(|| BeacnError::NotFound(format!("{} '{}'", kind, name)))?;
+
+        if *self.backend.lock() == Backend::PipeWire {
+            if let Some(id) = wpctl_node_id(&device) {
+                run_command("wpctl", &["set-default", &id.to_string()])?;
+                return Ok(());
+            }
+        }
+
+        run_pactl(&[command, name])?;
+        Ok(())
+    }
+
+    fn device_exists(&self, name: &str, is_output: bool) -> bool {
+        self.get_audio_devices().iter().any(|d| d.is_output() == is_output && d.name == name)
+    }
+
+    fn load_tracked_module(&self, module: &str, args: &[&str]) -> Result<u32, BeacnError> {
+        let id = load_module_untracked(module, args)?;
+        self.own_modules.lock().push(TrackedModule {
+            id,
+            module: module.to_string(),
+            args: args.iter().map(|a| a.to_string()).collect(),
+        });
+        Ok(id)
+    }
+
+    fn unload_tracked_module(&self, module_id: u32) -> Result<(), BeacnError> {
+        run_pactl(&["unload-module", &module_id.to_string()])?;
+        self.own_modules.lock().retain(|m| m.id != module_id);
+        Ok(())
+    }
+
+    fn unload_own_modules(&self) -> Result<(), BeacnError> {
+        let links = std::mem::take(&mut *self.own_links.lock());
+        let mut first_error = unlink_routes(&links).err();
+
+        let mut modules = self.own_modules.lock();
+
+        while let Some(tracked) = modules.pop() {
+            if let Err(e) = run_pactl(&["unload-module", &tracked.id.to_string()]) {
+                first_error.get_or_insert(e);
+            }
+        }
+
+        first_error.map_or(Ok(()), Err)
+    }
+
+    /// Unloads the tracked `module` that created `sink`, lets `edit` change its
+    /// arguments and loads it again under the same name, then reloads the tracked
+    /// routes through it. If the edited sink won't load, the old one is loaded again
+    /// and the error returned. Returns the new module ID.
+    fn reload_tracked_sink(
+        &self,
+        module: &str,
+        sink: &str,
+        edit: impl FnOnce(&mut Vec<String>) -> Result<(), BeacnError>,
+    ) -> Result<u32, BeacnError> {
+        let reloaded = {
+            let mut modules = self.own_modules.lock();
+            let position = modules
+                .iter()
+                .position(|m| m.module == module && module_arg(&parse_module_args(&m.args.join(" ")), "sink_name") == Some(sink))
+                .ok_or_else(|| {
+                    BeacnError::Unsupported(format!("reconfiguring '{}', which BEACN Link didn't create with {}", sink, module))
+                })?;
+
+            let mut args = modules[position].args.clone();
+            edit(&mut args)?;
+
+            let old_id = modules[position].id;
+            run_pactl(&["unload-module", &old_id.to_string()])?;
+
+            // The server would move their streams to the fallback device and leave
+            // them there, so routes through the sink are unloaded and loaded again
+            for dependent in modules.iter().filter(|m| m.id != old_id && m.uses_sink(sink)) {
+                if let Err(e) = run_pactl(&["unload-module", &dependent.id.to_string()]) {
+                    logging::debug(format_args!("Module {} went away with {}: {}", dependent.id, sink, e));
+                }
+            }
+
+            let tracked = &mut modules[position];
+            let edited: Vec<&str> = args.iter().map(String::as_str).collect();
+            match load_module_untracked(&tracked.module, &edited) {
+                Ok(id) => {
+                    tracked.id = id;
+                    tracked.args = args;
+                    Ok(id)
+                }
+                Err(e) => {
+                    let old: Vec<&str> = tracked.args.iter().map(String::as_str).collect();
+                    match load_module_untracked(&tracked.module, &old) {
+                        Ok(id) => tracked.id = id,
+                        Err(e) => logging::error(format_args!("Could not load '{}' again as it was: {}", sink, e)),
+                    }
+                    Err(e)
+                }
+            }
+        };
+
+        let restored = self.restore_tracked_modules();
+        let id = reloaded?;
+        restored?;
+        Ok(id)
+    }
+
+    fn reload_combined_sink(
+        &self,
+        combined_sink: &str,
+        edit: impl FnOnce(&mut Vec<String>) -> Result<(), BeacnError>,
+    ) -> Result<CombinedSinkUpdate, BeacnError> {
+        let id = self.reload_tracked_sink("module-combine-sink", combined_sink, |args| {
+            let Some(position) = args.iter().position(|a| a.starts_with("slaves=")) else {
+                return Err(BeacnError::Unsupported(format!("changing the members of '{}'", combined_sink)));
+            };
+            let mut members: Vec<String> = args[position]["slaves=".len()..].split(',').map(str::to_string).collect();
+            edit(&mut members)?;
+            args[position] = format!("slaves={}", members.join(","));
+            Ok(())
+        })?;
+
+        logging::info(format_args!("Reloaded combined sink {} as module {}", combined_sink, id));
+        Ok(CombinedSinkUpdate { module_id: id, reloaded: true })
+    }
+
+    /// `sink_name` of every tracked module that created a sink, in load order.
+    fn tracked_sinks(&self) -> Vec<String> {
+        self.own_modules
+            .lock()
+            .iter()
+            .filter_map(|m| module_arg(&parse_module_args(&m.args.join(" ")), "sink_name").map(str::to_string))
+            .collect()
+    }
+
+    fn for_each_tracked_sink(&self, apply: impl Fn(String) -> Result<(), BeacnError>) -> SinkBatchReport {
+        let mut report = SinkBatchReport { succeeded: Vec::new(), failed: Vec::new() };
+        for sink in self.tracked_sinks() {
+            match apply(sink.clone()) {
+                Ok(()) => report.succeeded.push(sink),
+                Err(e) => report.failed.push(format!("{} ({})", sink, e)),
+            }
+        }
+        report
+    }
+
+    /// Reloads tracked modules that are no longer on the server, oldest first so
+    /// sinks exist again before the loopbacks that feed them.
+    fn restore_tracked_modules(&self) -> Result<(), BeacnError> {
+        self.ensure_initialized()?;
+        restore_modules(&self.own_modules)?;
+        restore_links(&self.own_links);
+        Ok(())
+    }
+
+    /// Links `source` to `sink` port by port with `pw-link` and tracks it as a route.
+    /// A monitor is its sink's node, whose output ports are the monitor ports.
+    fn link_route(&self, source: String, sink: String) -> Result<(), BeacnError> {
+        let node = source.strip_suffix(".monitor").unwrap_or(&source);
+        let outputs = parse_pw_ports(&run_command("pw-link", &["--output"])?, node);
+        let inputs = parse_pw_ports(&run_command("pw-link", &["--input"])?, &sink);
+
+        let ports = pair_pw_ports(&outputs, &inputs);
+        if ports.is_empty() {
+            return Err(BeacnError::NotFound(format!("PipeWire ports to link {} -> {}", source, sink)));
+        }
+
+        for (done, (output, input)) in ports.iter().enumerate() {
+            if let Err(e) = run_command("pw-link", &[output, input]) {
+                let _ = unlink_ports(&ports[..done]);
+                return Err(e);
+            }
+        }
+
+        self.own_links.lock().push(TrackedLink { source, sink, ports });
+        Ok(())
+    }
+
+    fn remove_listed_route(&self, route: &RouteInfo) -> Result<(), BeacnError> {
+        if let Some(id) = route.module_id {
+            return self.unload_tracked_module(id);
+        }
+
+        let mut links = self.own_links.lock();
+        let same = |l: &TrackedLink| {
+            Some(l.source.as_str()) == route.source.as_deref() && Some(l.sink.as_str()) == route.sink.as_deref()
+        };
+        if let Some(position) = links.iter().position(same) {
+            unlink_ports(&links[position].ports)?;
+            links.remove(position);
+        }
+        Ok(())
+    }
+}
+
+/// A `pw-link` route made by this instance: its ends as given to `route_audio` and
+/// the `(output, input)` port pairs linked for it.
+struct TrackedLink {
+    source: String,
+    sink: String,
+    ports: Vec<(String, String)>,
+}
+
+/// A module loaded by this instance, with enough detail to load it again.
+struct TrackedModule {
+    id: u32,
+    module: String,
+    args: Vec<String>,
+}
+
+impl TrackedModule {
+    /// Whether `block` is this module: same index, module and arguments. A
+    /// restarted server reuses low indices, so the index alone proves nothing.
+    fn matches(&self, block: &Block) -> bool {
+        block.index == self.id
+            && block.name() == Some(self.module.as_str())
+            && block.field("Argument").unwrap_or("") == self.args.join(" ")
+    }
+
+    /// Whether this module streams from or into `sink` or its monitor, like a
+    /// loopback or a remap source on top of it.
+    fn uses_sink(&self, sink: &str) -> bool {
+        let monitor = format!("{}.monitor", sink);
+        let args = parse_module_args(&self.args.join(" "));
+        ["sink", "source", "master"]
+            .iter()
+            .any(|key| module_arg(&args, key).is_some_and(|value| value == sink || value == monitor))
+    }
+}
+
+/// Fields shared by sink inputs and source outputs.
+struct Stream {
+    id: u32,
+    app_name: Option<String>,
+    media_name: Option<String>,
+    device: Option<String>,
+    volume_percent: u32,
+    muted: bool,
+}
+
+/// Connects to the server and installs the connection into a `BeacnLink`'s shared
+/// state. Run inline by `initialize_with_server` and on the threadpool by
+/// `initialize_async`.
+pub struct ConnectTask {
+    server: Option<String>,
+    iterations: u32,
+    pulse_context: Arc<Mutex<Option<Context>>>,
+    pulse_mainloop: Arc<Mutex<Option<OwnedMainloop>>>,
+    backend: Arc<Mutex<Backend>>,
+}
+
+impl ConnectTask {
+    fn connect(&self) -> Result<(), BeacnError> {
+        // Check if PulseAudio (or PipeWire's pulse server) is running. The local
+        // daemon checks say nothing about a remote server, so those are skipped.
+        let local_backend = match self.server {
+            Some(_) => None,
+            None => match detect_backend() {
+                Backend::Unknown => {
+                    return Err(BeacnError::ConnectionFailed("neither PulseAudio nor PipeWire is running".to_string()))
+                }
+                backend => Some(backend),
+            },
+        };
+
+        let (mut mainloop, context) = connect_context(self.server.as_deref(), self.iterations)?;
+
+        set_pulse_server(self.server.clone());
+        let backend = local_backend.unwrap_or_else(|| {
+            pactl_info_or_native(&mut mainloop, &context)
+                .ok()
+                .and_then(|mut info| info.remove("Server Name"))
+                .map_or(Backend::Unknown, |name| backend_from_server_name(&name))
+        });
+
+        // Swap both under their locks, in `with_connection`'s order, so nothing can
+        // pair the new context with the old mainloop. The old context drops first.
+        let mut mainloop_slot = self.pulse_mainloop.lock();
+        let mut context_slot = self.pulse_context.lock();
+        *context_slot = Some(context);
+        *mainloop_slot = Some(OwnedMainloop(mainloop));
+        *self.backend.lock() = backend;
+        Ok(())
+    }
+}
+
+#[napi]
+impl Task for ConnectTask {
+    type Output = bool;
+    type JsValue = bool;
+
+    fn compute(&mut self) -> napi::Result<Self::Output> {
+        match self.connect() {
+            Ok(()) => Ok(true),
+            Err(e) => {
+                logging::error(format_args!("{}", e));
+                Ok(false)
+            }
+        }
+    }
+
+    fn resolve(&mut self, _env: Env, output: Self::Output) -> napi::Result<Self::JsValue> {
+        Ok(output)
+    }
+}
+
+pub struct ListDevicesTask {
+    server: Option<String>,
+    iterations: u32,
+}
+
+impl ListDevicesTask {
+    // The main connection can't leave the JS thread, so the task opens its own
+    fn native(&self) -> Result<Vec<AudioDevice>, BeacnError> {
+        let (mut mainloop, mut context) = connect_context(self.server.as_deref(), self.iterations)?;
+        let devices = native_devices(&mut mainloop, &context);
+        context.disconnect();
+        devices
+    }
+}
+
+#[napi]
+impl Task for ListDevicesTask {
+    type Output = Vec<AudioDevice>;
+    type JsValue = Vec<AudioDevice>;
+
+    fn compute(&mut self) -> napi::Result<Self::Output> {
+        match self.native() {
+            Ok(devices) => Ok(devices),
+            Err(e) => {
+                logging::debug(format_args!("Listing devices through pactl: {}", e));
+                Ok(list_devices())
+            }
+        }
+    }
+
+    fn resolve(&mut self, _env: Env, output: Self::Output) -> napi::Result<Self::JsValue> {
+        Ok(output)
+    }
+}
+
+impl Drop for BeacnLink {
+    fn drop(&mut self) {
+        if self.cleanup_on_drop {
+            // Also after `shutdown`, which leaves the modules loaded
+            let _ = self.unload_own_modules();
+        }
+    }
+}
+
+impl Default for BeacnLink {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn validate_volume(volume_percent: u32) -> Result<(), BeacnError> {
+    if volume_percent > MAX_VOLUME_PERCENT {
+        return Err(BeacnError::InvalidArgument(format!(
+            "volume {}% exceeds the maximum of {}%",
+            volume_percent, MAX_VOLUME_PERCENT
+        )));
+    }
+    Ok(())
+}
+
+fn load_module_untracked(module: &str, args: &[&str]) -> Result<u32, BeacnError> {
+    let mut argv = vec!["load-module", module];
+    argv.extend_from_slice(args);
+    parse_module_id(&argv, &run_pactl(&argv)?)
+}
+
+/// pactl prints the index of the newly loaded module on stdout. Take the last token,
+/// in case a server (or a wrapper script) prints anything before it.
+fn parse_module_id(argv: &[&str], stdout: &str) -> Result<u32, BeacnError> {
+    stdout.split_whitespace().next_back().and_then(|id| id.parse().ok()).ok_or_else(|| BeacnError::CommandFailed {
+        command: command_line("pactl", argv),
+        code: Some(0),
+        stderr: format!("unexpected load-module output: {}", stdout),
+    })
+}
+
+fn routing_dot(devices: &[AudioDeviceDetail], routes: &[RouteInfo]) -> String {
+    let escape = |s: &str| s.replace('\\', "\\\\").replace('"', "\\\"");
+    let mut dot = String::from("digraph beacn_link {\n    rankdir=LR;\n");
+
+    for device in devices {
+        let shape = match device.kind {
+            DeviceKind::Sink => "shape=box",
+            DeviceKind::Source => "shape=ellipse",
+            DeviceKind::MonitorSource => "shape=ellipse, style=dashed",
+        };
+        let mute = if device.muted { ", muted" } else { "" };
+        dot.push_str(&format!(
+            "    \"{}\" [{}, label=\"{}\\n{}%{}\"];\n",
+            escape(&device.name),
+            shape,
+            escape(&device.description),
+            device.volume_percent,
+            mute
+        ));
+    }
+
+    for route in routes {
+        if let (Some(source), Some(sink)) = (&route.source, &route.sink) {
+            let latency = route.latency_msec.map(|l| format!(" {}ms", l)).unwrap_or_default();
+            let label = route.module_id.map_or("pw-link".to_string(), |id| format!("#{}", id));
+            dot.push_str(&format!(
+                "    \"{}\" -> \"{}\" [label=\"{}{}\"];\n",
+                escape(source),
+                escape(sink),
+                label,
+                latency
+            ));
+        }
+    }
+
+    dot.push_str("}\n");
+    dot
+}
+
+fn parse_ip(ip: &str) -> Result<std::net::IpAddr, BeacnError> {
+    ip.parse().map_err(|_| BeacnError::InvalidArgument(format!("{:?} is not an IP address", ip)))
+}
+
+/// Checks a PULSE_SERVER style address: `host`, `host:port`, `[ipv6]:port`, each
+/// optionally prefixed with `tcp:`, `tcp4:` or `tcp6:`, or `unix:/path/to/socket`.
+fn validate_server_address(server: &str) -> Result<(), BeacnError> {
+    let invalid = |why: &str| Err(BeacnError::InvalidArgument(format!("server address {:?} {}", server, why)));
+
+    if server.is_empty() {
+        return invalid("is empty");
+    }
+
+    // It ends up unquoted in a module argument, so anything that would split it is out
+    if server.chars().any(|c| c.is_whitespace() || c.is_control() || matches!(c, '\'' | '"' | '\\')) {
+        return invalid("contains whitespace, quotes or backslashes");
+    }
+
+    if let Some(path) = server.strip_prefix("unix:") {
+        return if path.starts_with('/') { Ok(()) } else { invalid("needs an absolute socket path") };
+    }
+
+    let address = ["tcp4:", "tcp6:", "tcp:"].iter().find_map(|p| server.strip_prefix(p)).unwrap_or(server);
+    let (host, port) = match address.strip_prefix('[') {
+        Some(bracketed) => match bracketed.split_once(']') {
+            Some((host, rest)) => (host, rest.strip_prefix(':')),
+            None => return invalid("has an unclosed '['"),
+        },
+        None => match address.split_once(':') {
+            Some((host, port)) => (host, Some(port)),
+            None => (address, None),
+        },
+    };
+
+    if host.is_empty() {
+        return invalid("has no host");
+    }
+
+    if port.is_some_and(|p| p.parse::<u16>().is_err()) {
+        return invalid("has an invalid port");
+    }
+
+    Ok(())
+}
+
+/// Whether `<plugin>.so` is in LADSPA_PATH, or one of the usual directories if unset.
+fn ladspa_plugin_installed(plugin: &str) -> bool {
+    let file = format!("{}.so", plugin);
+    match std::env::var("LADSPA_PATH") {
+        Ok(path) => path.split(':').any(|dir| std::path::Path::new(dir).join(&file).is_file()),
+        Err(_) => LADSPA_DIRS.iter().any(|dir| std::path::Path::new(dir).join(&file).is_file()),
+    }
+}
+
+/// Checks a comma separated channel map against the known position names and
+/// `channels`, returning it without whitespace.
+fn channel_map_arg(map: &str, channels: u8) -> Result<String, BeacnError> {
+    let positions: Vec<&str> = map.split(',').map(str::trim).filter(|p| !p.is_empty()).collect();
+
+    if positions.len() != channels as usize {
+        return Err(BeacnError::InvalidArgument(format!(
+            "channel map '{}' has {} positions for {} channels",
+            map,
+            positions.len(),
+            channels
+        )));
+    }
+
+    let is_aux = |p: &str| p.strip_prefix("aux").and_then(|n| n.parse::<u8>().ok()).is_some_and(|n| n < 32);
+    if let Some(unknown) = positions.iter().find(|p| !CHANNEL_POSITIONS.contains(p) && !is_aux(p)) {
+        return Err(BeacnError::InvalidArgument(format!("'{}' in channel map '{}' is not a channel position", unknown, map)));
+    }
+
+    Ok(positions.join(","))
+}
+
+/// Parses the `Ports:` section of a sink or source block.
+fn device_ports(block: &Block) -> Vec<PortInfo> {
+    let active = block.field("Active Port");
+
+    block
+        .sections
+        .get("Ports")
+        .into_iter()
+        .flatten()
+        .filter_map(|line| parse_section_entry(line))
+        .map(|(name, description, details)| PortInfo {
+            name: name.to_string(),
+            description: description.to_string(),
+            // pactl only mentions availability when the driver knows it
+            availability: if details.contains(&"not available") {
+                "unavailable"
+            } else if details.contains(&"available") {
+                "available"
+            } else {
+                "unknown"
+            }
+            .to_string(),
+            priority: details.iter().find_map(|d| d.strip_prefix("priority: ")?.parse().ok()).unwrap_or(0),
+            active: active == Some(name),
+        })
+        .collect()
+}
+
+/// The explicit format a null sink was created with, if any.
+fn saved_format(args: &[(String, String)]) -> Option<FormatConfig> {
+    let format = FormatConfig {
+        channels: module_arg(args, "channels").and_then(|c| c.parse().ok()),
+        rate: module_arg(args, "rate").and_then(|r| r.parse().ok()),
+        format: module_arg(args, "format").map(str::to_string),
+        channel_map: module_arg(args, "channel_map").map(str::to_string),
+    };
+
+    let saved = format.channels.is_some() || format.rate.is_some() || format.format.is_some() || format.channel_map.is_some();
+    saved.then_some(format)
+}
+
+fn validate_link_output_count(count: u32) -> Result<(), BeacnError> {
+    if !LINK_OUTPUT_COUNT_RANGE.contains(&count) {
+        return Err(BeacnError::InvalidArgument(format!(
+            "{} outputs is outside {}-{}",
+            count,
+            LINK_OUTPUT_COUNT_RANGE.start(),
+            LINK_OUTPUT_COUNT_RANGE.end()
+        )));
+    }
+    Ok(())
+}
+
+/// `<prefix>_Out`, `<prefix>_2_Out`, `<prefix>_3_Out`, ...
+fn link_output_names(prefix: &str, count: u32) -> Vec<String> {
+    (1..=count)
+        .map(|i| match i {
+            1 => format!("{}_Out", prefix),
+            _ => format!("{}_{}_Out", prefix, i),
+        })
+        .collect()
+}
+
+/// Finds the `module-null-sink` loaded with exactly `sink_name=<name>`.
+fn find_null_sink_module(name: &str) -> Result<Option<u32>, BeacnError> {
+    Ok(null_sink_module(&list_modules()?, name))
+}
+
+fn null_sink_module(modules: &[Block], name: &str) -> Option<u32> {
+    modules
+        .iter()
+        .find(|b| b.name() == Some("module-null-sink") && module_arg(&b.module_args(), "sink_name") == Some(name))
+        .map(|b| b.index)
+}
+
+// napi has no u64 conversion; an i64 reaches JS as a plain number
+fn device_latency(block: &Block, name: &str) -> Result<i64, BeacnError> {
+    block
+        .field("Latency")
+        .and_then(parse_latency_usec)
+        .map(|usec| usec as i64)
+        .ok_or_else(|| BeacnError::NotFound(format!("latency of '{}'", name)))
+}
+
+fn restore_modules(own_modules: &Mutex<Vec<TrackedModule>>) -> Result<(), BeacnError> {
+    let loaded = list_modules()?;
+    let mut modules = own_modules.lock();
+
+    for tracked in modules.iter_mut() {
+        if loaded.iter().any(|b| tracked.matches(b)) {
+            continue;
+        }
+        let args: Vec<&str> = tracked.args.iter().map(String::as_str).collect();
+        tracked.id = load_module_untracked(&tracked.module, &args)?;
+    }
+
+    Ok(())
+}
+
+/// Disconnects the pairs in `ports` that are still linked. Ones PipeWire already
+/// dropped, e.g. with a removed node, are skipped.
+fn unlink_ports(ports: &[(String, String)]) -> Result<(), BeacnError> {
+    let live = parse_pw_links(&run_command("pw-link", &["--links"])?);
+    for (output, input) in ports {
+        if live.iter().any(|l| l.output_port == *output && l.input_port == *input) {
+            run_command("pw-link", &["--disconnect", output, input])?;
+        }
+    }
+    Ok(())
+}
+
+/// Disconnects every route in `links`, going on past failures; returns the first.
+fn unlink_routes(links: &[TrackedLink]) -> Result<(), BeacnError> {
+    let mut first_error = None;
+    for link in links {
+        if let Err(e) = unlink_ports(&link.ports) {
+            first_error.get_or_insert(e);
+        }
+    }
+    first_error.map_or(Ok(()), Err)
+}
+
+/// Links again any tracked port pair PipeWire no longer has, e.g. after a restart
+/// or once a reloaded sink's ports are back.
+fn restore_links(own_links: &Mutex<Vec<TrackedLink>>) {
+    let links = own_links.lock();
+    if links.is_empty() {
+        return;
+    }
+
+    let live = match run_command("pw-link", &["--links"]) {
+        Ok(output) => parse_pw_links(&output),
+        Err(e) => {
+            logging::warn(format_args!("Could not check PipeWire routes: {}", e));
+            return;
+        }
+    };
+
+    for (output, input) in links.iter().flat_map(|l| &l.ports) {
+        if live.iter().any(|l| l.output_port == *output && l.input_port == *input) {
+            continue;
+        }
+        if let Err(e) = run_command("pw-link", &[output, input]) {
+            logging::warn(format_args!("Could not restore PipeWire link {} -> {}: {}", output, input, e));
+        }
+    }
+}
+
+/// The PipeWire node ID `wpctl status` lists for `device`. wpctl shows descriptions,
+/// so a device sharing its description with another can't be picked out.
+fn wpctl_node_id(device: &AudioDevice) -> Option<u32> {
+    let status = run_command("wpctl", &["status"]).ok()?;
+    let section = if device.is_output() { "Sinks" } else { "Sources" };
+    let matching: Vec<u32> = parse_wpctl_nodes(&status, section)
+        .into_iter()
+        .filter(|(_, label)| *label == device.description || *label == device.name)
+        .map(|(id, _)| id)
+        .collect();
+
+    match matching.as_slice() {
+        [id] => Some(*id),
+        _ => None,
+    }
+}
+
+/// Index of the sink input a loopback module plays into its sink through.
+fn route_sink_input(route_module_id: u32) -> Result<u32, BeacnError> {
+    let owner = route_module_id.to_string();
+    parse_blocks(&run_pactl(&["list", "sink-inputs"])?, "Sink Input")
+        .into_iter()
+        .find(|b| b.field("Owner Module") == Some(owner.as_str()))
+        .map(|b| b.index)
+        .ok_or_else(|| BeacnError::NotFound(format!("stream of route {}", route_module_id)))
+}
+
+/// Devices from the `pactl` listings, for callers without a connection of their own.
+fn list_devices() -> Vec<AudioDevice> {
+    let mut devices = Vec::new();
+
+    // The long listings carry the format and monitor details the short ones lack
+    for (kind, header, is_output) in [("sinks", "Sink", true), ("sources", "Source", false)] {
+        if let Ok(output) = run_pactl(&["list", kind]) {
+            devices.extend(parse_devices(&output, header, is_output));
+        }
+    }
+
+    devices
+}
+
+fn pactl_info_or_native(mainloop: &mut Mainloop, context: &Context) -> Result<HashMap<String, String>, BeacnError> {
+    match run_pactl(&["info"]) {
+        Err(BeacnError::PactlNotFound) => native_info(mainloop, context),
+        info => info.map(|i| parse_info(&i)),
+    }
+}
+
+/// Builds devices from `pactl list sinks|sources`, skipping entries without a name.
+fn parse_devices(output: &str, header: &str, is_output: bool) -> Vec<AudioDevice> {
+    parse_blocks(output, header)
+        .iter()
+        .filter_map(|block| {
+            let Some(name) = block.name() else {
+                logging::warn(format_args!("Skipping {} #{}, which has no name", header, block.index));
+                return None;
+            };
+            let (_, channels, sample_rate) =
+                block.field("Sample Specification").and_then(parse_sample_spec).unwrap_or_default();
+            let monitor_of = block.field("Monitor of Sink").filter(|s| !is_output && *s != "n/a");
+
+            Some(AudioDevice {
+                name: name.to_string(),
+                id: block.index.to_string(),
+                index: block.index,
+                description: block.field("Description").unwrap_or("").to_string(),
+                kind: match (is_output, monitor_of.is_some()) {
+                    (true, _) => DeviceKind::Sink,
+                    (false, true) => DeviceKind::MonitorSource,
+                    (false, false) => DeviceKind::Source,
+                },
+                is_monitor: monitor_of.is_some(),
+                monitor_of: monitor_of.map(str::to_string),
+                sample_rate,
+                channels: channels as u8,
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn link_output_names_keep_the_classic_scheme() {
+        assert_eq!(
+            link_output_names("BEACN_Link", 4),
+            ["BEACN_Link_Out", "BEACN_Link_2_Out", "BEACN_Link_3_Out", "BEACN_Link_4_Out"]
+        );
+        assert_eq!(link_output_names("Mix", 2), ["Mix_Out", "Mix_2_Out"]);
+        assert!(link_output_names("Mix", 0).is_empty());
+    }
+
+    #[test]
+    fn link_output_count_is_bounded() {
+        assert!(validate_link_output_count(1).is_ok());
+        assert!(validate_link_output_count(32).is_ok());
+        assert!(validate_link_output_count(0).is_err());
+        assert!(validate_link_output_count(33).is_err());
+        assert!(validate_link_output_count(u32::MAX).is_err());
+    }
+
+    #[test]
+    fn null_sinks_are_found_by_exact_sink_name() {
+        let listing = "Module #26\n\
+            \tName: module-null-sink\n\
+            \tArgument: sink_name=BEACN_Link_Out_2 sink_properties='device.description=\"BEACN_Link_Out_2\"'\n\
+            \tUsage counter: n/a\n\
+            \n\
+            Module #27\n\
+            \tName: module-null-sink\n\
+            \tArgument: sink_name=BEACN_Link_Out sink_properties='device.description=\"BEACN_Link_Out\"'\n\
+            \tUsage counter: n/a\n\
+            \n\
+            Module #28\n\
+            \tName: module-remap-sink\n\
+            \tArgument: sink_name=BEACN_Link_Remap master=BEACN_Link_Out\n";
+        let modules = parse_blocks(listing, "Module");
+
+        assert_eq!(null_sink_module(&modules, "BEACN_Link_Out"), Some(27));
+        assert_eq!(null_sink_module(&modules, "BEACN_Link_Out_2"), Some(26));
+        assert_eq!(null_sink_module(&modules, "BEACN_Link"), None);
+        assert_eq!(null_sink_module(&modules, "BEACN_Link_Remap"), None);
+    }
+
+    #[test]
+    fn module_id_is_the_trailing_integer() {
+        let argv = ["load-module", "module-null-sink"];
+        assert_eq!(parse_module_id(&argv, "536870913\n").unwrap(), 536870913);
+        assert_eq!(parse_module_id(&argv, "W: some warning\n27\n").unwrap(), 27);
+        assert!(parse_module_id(&argv, "").is_err());
+        assert!(parse_module_id(&argv, "Failure: Module initialization failed").is_err());
+        assert!(parse_module_id(&argv, "").unwrap_err().to_string().contains("`pactl load-module module-null-sink`"));
+    }
+
+    #[test]
+    fn dot_graph_has_shaped_nodes_and_route_edges() {
+        let device = |name: &str, kind: DeviceKind, muted: bool| AudioDeviceDetail {
+            index: 0,
+            name: name.to_string(),
+            description: format!("{} \"desc\"", name),
+            is_output: kind == DeviceKind::Sink,
+            kind,
+            channels: 2,
+            sample_rate: 48000,
+            sample_format: "s16le".to_string(),
+            state: "IDLE".to_string(),
+            volume_percent: 80,
+            muted,
+            monitor_source: None,
+        };
+        let devices = [device("Out", DeviceKind::Sink, true), device("Out.monitor", DeviceKind::MonitorSource, false)];
+        let routes = [RouteInfo {
+            module_id: Some(7),
+            source: Some("Out.monitor".to_string()),
+            sink: Some("Out".to_string()),
+            latency_msec: Some(20),
+            resampling: false,
+            valid: true,
+            remix: true,
+        }];
+
+        let dot = routing_dot(&devices, &routes);
+        assert!(dot.starts_with("digraph beacn_link {"));
+        assert!(dot.contains(r#""Out" [shape=box, label="Out \"desc\"\n80%, muted"];"#));
+        assert!(dot.contains(r#""Out.monitor" [shape=ellipse, style=dashed, label="Out.monitor \"desc\"\n80%"];"#));
+        assert!(dot.contains(r##""Out.monitor" -> "Out" [label="#7 20ms"];"##));
+    }
+
+    #[test]
+    fn server_addresses_are_checked() {
+        for ok in ["studio-pc", "tcp:studio-pc:4713", "192.168.1.20:4713", "tcp6:[fe80::1]:4713", "unix:/run/pulse/native"] {
+            assert!(validate_server_address(ok).is_ok(), "{} should be accepted", ok);
+        }
+        for bad in ["", "tcp:", "host:port", "unix:relative", "[::1", "a b", "host'"] {
+            assert!(validate_server_address(bad).is_err(), "{:?} should be rejected", bad);
+        }
+    }
+
+    #[test]
+    fn channel_maps_are_checked_and_compacted() {
+        assert_eq!(channel_map_arg("front-left, front-right", 2).unwrap(), "front-left,front-right");
+        assert_eq!(channel_map_arg("aux0,aux31,mono", 3).unwrap(), "aux0,aux31,mono");
+        assert!(channel_map_arg("front-left", 2).is_err());
+        assert!(channel_map_arg("aux32", 1).is_err());
+        assert!(channel_map_arg("middle", 1).is_err());
+    }
+
+    #[test]
+    fn devices_come_from_the_long_listing() {
+        let output = "Source #3\n\
+                      \tState: SUSPENDED\n\
+                      \tName: alsa_input.usb-BEACN_Mic-00.mono-fallback\n\
+                      \tDescription: BEACN Mic Mono\n\
+                      \tSample Specification: s24le 1ch 48000Hz\n\
+                      \tMonitor of Sink: n/a\n\
+                      Source #4\n\
+                      \tName: BEACN_Link_Out.monitor\n\
+                      \tDescription: Monitor of BEACN Link Out\n\
+                      \tSample Specification: s16le 2ch 44100Hz\n\
+                      \tMonitor of Sink: BEACN_Link_Out\n\
+                      Source #5\n\
+                      \tDescription: nameless\n";
+
+        let devices = parse_devices(output, "Source", false);
+        assert_eq!(devices.len(), 2);
+
+        assert_eq!(devices[0].id, "3");
+        assert_eq!(devices[0].index, 3);
+        assert_eq!(devices[0].description, "BEACN Mic Mono");
+        assert_eq!(devices[0].kind, DeviceKind::Source);
+        assert_eq!((devices[0].sample_rate, devices[0].channels), (48000, 1));
+        assert_eq!(devices[0].monitor_of, None);
+
+        assert_eq!(devices[1].kind, DeviceKind::MonitorSource);
+        assert!(devices[1].is_monitor);
+        assert_eq!(devices[1].monitor_of.as_deref(), Some("BEACN_Link_Out"));
+        assert_eq!((devices[1].sample_rate, devices[1].channels), (44100, 2));
+        assert!(!devices[1].is_output());
+    }
+
+    #[test]
+    fn saved_formats_keep_whatever_was_given() {
+        let surround = parse_module_args("sink_name=Cinema channels=6 channel_map=front-left,front-right,rear-left");
+        let format = saved_format(&surround).unwrap();
+        assert_eq!(format.channels, Some(6));
+        assert_eq!(format.channel_map.as_deref(), Some("front-left,front-right,rear-left"));
+        assert_eq!((format.rate, format.format), (None, None));
+
+        assert!(saved_format(&parse_module_args("sink_name=BEACN_Link_Out")).is_none());
+    }
+
+    #[test]
+    fn routes_through_a_sink_or_its_monitor_depend_on_it() {
+        let module = |module: &str, args: &[&str]| TrackedModule {
+            id: 0,
+            module: module.to_string(),
+            args: args.iter().map(|a| a.to_string()).collect(),
+        };
+
+        assert!(module("module-loopback", &["source=BEACN_Link_Out.monitor", "sink=hw"]).uses_sink("BEACN_Link_Out"));
+        assert!(module("module-loopback", &["source=mic", "sink=BEACN_Link_Out"]).uses_sink("BEACN_Link_Out"));
+        assert!(module("module-remap-source", &["master=BEACN_Link_Out.monitor"]).uses_sink("BEACN_Link_Out"));
+        assert!(!module("module-loopback", &["source=BEACN_Link_Out_2.monitor"]).uses_sink("BEACN_Link_Out"));
+        assert!(!module("module-null-sink", &["sink_name=BEACN_Link_Out"]).uses_sink("BEACN_Link_Out"));
     }
 }
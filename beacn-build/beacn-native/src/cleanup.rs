@@ -1,24 +1,109 @@
 use napi_derive::napi;
 
+use crate::error::BeacnError;
+use crate::logging;
+use crate::pactl::{module_arg, parse_module_args, run_pactl};
+
+// Devices BEACN Link creates are named with this prefix, in any case
+const DEVICE_NAME_PREFIX: &str = "beacn_link";
+// Tag `route_audio` puts in a loopback's stream properties
+const ROUTE_MARKER: &str = "beacn.link.route=1";
+
+#[napi(object)]
+pub struct CleanupReport {
+    pub unloaded: Vec<u32>,
+    pub failed: Vec<FailedUnload>,
+}
+
+#[napi(object)]
+pub struct FailedUnload {
+    pub module_id: u32,
+    pub error: String,
+}
+
+#[napi(object)]
+pub struct ModulePreview {
+    pub module_id: u32,
+    pub name: String,
+    pub arguments: String,
+}
+
+/// Unloads every module that created a `BEACN_Link*` sink or source, and every
+/// loopback `route_audio` made, as found in `pactl list short modules`.
+/// A missing `pactl` is reported as `PactlNotFound` instead of aborting the process.
 #[napi]
-pub fn cleanup_virtual_devices() -> bool {
+pub fn cleanup_virtual_devices() -> Result<CleanupReport, BeacnError> {
     // Get list of BEACN Link module IDs
-    let output = std::process::Command::new("pactl")
-        .args(["list", "short", "modules"])
-        .output()
-        .expect("Failed to execute pactl");
+    let modules = run_pactl(&["list", "short", "modules"])?;
+    let mut report = CleanupReport { unloaded: Vec::new(), failed: Vec::new() };
 
-    let modules = String::from_utf8_lossy(&output.stdout);
-    
     // Find and unload BEACN Link modules
-    for line in modules.lines() {
-        if line.contains("beacn_link_") {
-            if let Some(module_id) = line.split('\t').next() {
-                let _ = std::process::Command::new("pactl")
-                    .args(["unload-module", module_id])
-                    .output();
-            }
+    for module_id in target_modules(&modules).into_iter().map(|m| m.module_id) {
+        match run_pactl(&["unload-module", &module_id.to_string()]) {
+            Ok(_) => report.unloaded.push(module_id),
+            Err(e) => report.failed.push(FailedUnload { module_id, error: e.to_string() }),
+        }
+    }
+    Ok(report)
+}
+
+/// Lists the modules `cleanup_virtual_devices` would unload, without touching them.
+/// Returns an empty list if `pactl` can't be run.
+#[napi]
+pub fn cleanup_virtual_devices_dry_run() -> Vec<ModulePreview> {
+    match run_pactl(&["list", "short", "modules"]) {
+        Ok(modules) => target_modules(&modules),
+        Err(e) => {
+            logging::warn(format_args!("{}", e));
+            Vec::new()
         }
     }
-    true
+}
+
+fn target_modules(modules: &str) -> Vec<ModulePreview> {
+    modules
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split('\t');
+            Some(ModulePreview {
+                module_id: fields.next()?.trim().parse().ok()?,
+                name: fields.next().unwrap_or("").to_string(),
+                arguments: fields.next().unwrap_or("").to_string(),
+            })
+        })
+        .filter(|module| is_beacn_module(&module.arguments))
+        .collect()
+}
+
+fn is_beacn_module(arguments: &str) -> bool {
+    let args = parse_module_args(arguments);
+    let beacn_name = ["sink_name", "source_name"].iter().any(|key| {
+        module_arg(&args, key).is_some_and(|name| {
+            name.get(..DEVICE_NAME_PREFIX.len()).is_some_and(|p| p.eq_ignore_ascii_case(DEVICE_NAME_PREFIX))
+        })
+    });
+    beacn_name || arguments.contains(ROUTE_MARKER)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn targets_the_devices_and_routes_beacn_link_makes() {
+        let modules = "\
+0\tmodule-device-restore\t\t
+26\tmodule-null-sink\tsink_name=BEACN_Link_Out sink_properties='device.description=\"BEACN_Link_Out\"'\t
+27\tmodule-null-sink\tsink_name=BEACN_Link_2_Out sink_properties='device.description=\"BEACN_Link_2_Out\"'\t
+28\tmodule-loopback\tsource=BEACN_Link_Out.monitor sink=alsa_output.usb-BEACN_Mix-00.analog-stereo \
+source_output_properties=\"beacn.link.route=1 media.name='BEACN Link Route'\" \
+sink_input_properties=\"beacn.link.route=1 media.name='BEACN Link Route'\"\t
+29\tmodule-null-sink\tsink_name=beacn_link_legacy\t
+30\tmodule-null-sink\tsink_name=Game_Chat sink_properties='device.description=\"about BEACN_Link\"'\t
+31\tmodule-loopback\tsource=BEACN_Link_Out.monitor sink=other_sink\t
+";
+
+        let ids: Vec<u32> = target_modules(modules).into_iter().map(|m| m.module_id).collect();
+        assert_eq!(ids, [26, 27, 28, 29]);
+    }
 }
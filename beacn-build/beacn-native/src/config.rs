@@ -0,0 +1,72 @@
+use serde::{Deserialize, Serialize};
+use std::fs;
+
+use crate::error::BeacnError;
+
+// Bump when a change would make older builds misread the file
+pub const CONFIG_VERSION: u32 = 1;
+
+/// On-disk snapshot of a BEACN Link setup. Everything but `version` has a default,
+/// so files written by older builds still load; `read_config` turns away files
+/// from newer ones.
+#[derive(Serialize, Deserialize, Default)]
+pub struct LinkConfig {
+    pub version: u32,
+    #[serde(default)]
+    pub outputs: Vec<OutputConfig>,
+    #[serde(default)]
+    pub sources: Vec<SourceConfig>,
+    #[serde(default)]
+    pub routes: Vec<RouteConfig>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct OutputConfig {
+    pub name: String,
+    #[serde(default)]
+    pub format: Option<FormatConfig>,
+}
+
+// Each field is saved only if the sink was created with it
+#[derive(Serialize, Deserialize)]
+pub struct FormatConfig {
+    #[serde(default)]
+    pub channels: Option<u8>,
+    #[serde(default)]
+    pub rate: Option<u32>,
+    #[serde(default)]
+    pub format: Option<String>,
+    #[serde(default)]
+    pub channel_map: Option<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct SourceConfig {
+    pub name: String,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct RouteConfig {
+    pub source: String,
+    pub sink: String,
+    #[serde(default)]
+    pub latency_msec: Option<u32>,
+}
+
+pub fn write_config(path: &str, config: &LinkConfig) -> Result<(), BeacnError> {
+    let json = serde_json::to_string_pretty(config).map_err(|e| BeacnError::Config(e.to_string()))?;
+    fs::write(path, json).map_err(|e| BeacnError::Config(format!("could not write {}: {}", path, e)))
+}
+
+pub fn read_config(path: &str) -> Result<LinkConfig, BeacnError> {
+    let json = fs::read_to_string(path).map_err(|e| BeacnError::Config(format!("could not read {}: {}", path, e)))?;
+    let config: LinkConfig = serde_json::from_str(&json).map_err(|e| BeacnError::Config(format!("{}: {}", path, e)))?;
+
+    if config.version > CONFIG_VERSION {
+        return Err(BeacnError::Unsupported(format!(
+            "config version {} (this build reads up to {})",
+            config.version, CONFIG_VERSION
+        )));
+    }
+    Ok(config)
+}
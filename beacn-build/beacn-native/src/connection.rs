@@ -0,0 +1,69 @@
+use std::ops::{Deref, DerefMut};
+
+use libpulse_binding as pulse;
+use pulse::context::{Context, FlagSet, State};
+use pulse::mainloop::standard::{IterateResult, Mainloop};
+
+use crate::error::BeacnError;
+
+// Each connect iteration sleeps for CONNECT_POLL_MS, so the default gives up after ~5s
+pub const DEFAULT_CONNECT_ITERATIONS: u32 = 500;
+const CONNECT_POLL_MS: u64 = 10;
+
+/// A standard mainloop that can be handed to another thread as a whole. `Mainloop`
+/// is !Send only because its state sits in an `Rc`; the crate never keeps a clone
+/// of that `Rc` (no time events, no stored API handles), so the sole owner can move
+/// between threads as long as only one of them uses it at a time.
+pub struct OwnedMainloop(pub Mainloop);
+
+// SAFETY: see above; every OwnedMainloop is only ever reached through a Mutex
+unsafe impl Send for OwnedMainloop {}
+
+impl Deref for OwnedMainloop {
+    type Target = Mainloop;
+
+    fn deref(&self) -> &Mainloop {
+        &self.0
+    }
+}
+
+impl DerefMut for OwnedMainloop {
+    fn deref_mut(&mut self) -> &mut Mainloop {
+        &mut self.0
+    }
+}
+
+/// Creates a mainloop and context and drives them until the context is ready,
+/// giving up after `iterations` polls. Used for the main connection as well as the
+/// private ones background threads open.
+pub fn connect_context(server: Option<&str>, iterations: u32) -> Result<(Mainloop, Context), BeacnError> {
+    let target = server.unwrap_or("the local server");
+
+    let mut mainloop = Mainloop::new()
+        .ok_or_else(|| BeacnError::ConnectionFailed("could not create a PulseAudio mainloop".to_string()))?;
+
+    let mut context = Context::new(&mainloop, "BEACN Link")
+        .ok_or_else(|| BeacnError::ConnectionFailed("could not create a PulseAudio context".to_string()))?;
+
+    if let Err(e) = context.connect(server, FlagSet::NOFLAGS, None) {
+        return Err(BeacnError::ConnectionFailed(format!("connecting to {}: {}", target, e)));
+    }
+
+    // Drive the mainloop until the connection settles one way or the other
+    for _ in 0..iterations {
+        match mainloop.iterate(false) {
+            IterateResult::Quit(_) | IterateResult::Err(_) => break,
+            IterateResult::Success(_) => {}
+        }
+
+        match context.get_state() {
+            State::Ready => return Ok((mainloop, context)),
+            State::Failed | State::Terminated => break,
+            _ => std::thread::sleep(std::time::Duration::from_millis(CONNECT_POLL_MS)),
+        }
+    }
+
+    let state = context.get_state();
+    context.disconnect();
+    Err(BeacnError::ConnectionFailed(format!("{} did not become ready (state: {:?})", target, state)))
+}
@@ -0,0 +1,57 @@
+use std::fmt;
+
+#[derive(Debug)]
+pub enum BeacnError {
+    NotInitialized,
+    ConnectionFailed(String),
+    PactlNotFound,
+    /// `code` is `None` if the tool was killed by a signal or never started
+    CommandFailed { command: String, code: Option<i32>, stderr: String },
+    CommandTimedOut { command: String, timeout_ms: u32 },
+    DuplicateName(String),
+    InvalidArgument(String),
+    NotFound(String),
+    Unsupported(String),
+    Config(String),
+    Io(String),
+}
+
+impl fmt::Display for BeacnError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BeacnError::NotInitialized => write!(f, "BEACN Link is not initialized"),
+            BeacnError::ConnectionFailed(msg) => write!(f, "could not connect to PulseAudio: {}", msg),
+            BeacnError::PactlNotFound => write!(f, "pactl was not found on PATH"),
+            BeacnError::CommandFailed { command, code: Some(code), stderr } => {
+                write!(f, "`{}` failed with exit code {}: {}", command, code, stderr.trim())
+            }
+            BeacnError::CommandFailed { command, code: None, stderr } => {
+                write!(f, "`{}` failed: {}", command, stderr.trim())
+            }
+            BeacnError::CommandTimedOut { command, timeout_ms } => {
+                write!(f, "`{}` did not finish within {}ms", command, timeout_ms)
+            }
+            BeacnError::DuplicateName(name) => write!(f, "a device named '{}' already exists", name),
+            BeacnError::InvalidArgument(msg) => write!(f, "invalid argument: {}", msg),
+            BeacnError::NotFound(what) => write!(f, "{} not found", what),
+            BeacnError::Unsupported(what) => write!(f, "not supported: {}", what),
+            BeacnError::Config(msg) => write!(f, "config error: {}", msg),
+            BeacnError::Io(msg) => write!(f, "I/O error: {}", msg),
+        }
+    }
+}
+
+impl std::error::Error for BeacnError {}
+
+impl From<BeacnError> for napi::Error {
+    fn from(err: BeacnError) -> Self {
+        napi::Error::new(napi::Status::GenericFailure, err.to_string())
+    }
+}
+
+// Lets #[napi] methods return `Result<T, BeacnError>` directly and throw a JS `Error`
+impl From<BeacnError> for napi::JsError {
+    fn from(err: BeacnError) -> Self {
+        napi::JsError::from(napi::Error::from(err))
+    }
+}
@@ -0,0 +1,108 @@
+use napi_derive::napi;
+use std::fs;
+use std::path::Path;
+
+pub const USB_DEVICES_DIR: &str = "/sys/bus/usb/devices";
+pub const BEACN_VENDOR_ID: u16 = 0x33ae;
+
+#[napi(object)]
+pub struct BeacnDevice {
+    /// Product string reported by the device, e.g. `BEACN Mic`
+    pub product: String,
+    pub product_id: u32,
+    pub serial: Option<String>,
+    /// sysfs name of the USB device, e.g. `1-4.2`
+    pub usb_path: String,
+    /// ALSA card index; sinks on it carry the same `alsa.card` property
+    pub alsa_card: Option<u32>,
+    /// ALSA card ID, e.g. `Mic`
+    pub alsa_card_name: Option<String>,
+}
+
+/// Finds BEACN devices under a sysfs USB devices directory, sorted by USB path.
+pub fn scan_beacn_devices(root: &Path) -> Vec<BeacnDevice> {
+    let entries = match fs::read_dir(root) {
+        Ok(e) => e,
+        Err(_) => return Vec::new(),
+    };
+
+    let mut devices: Vec<BeacnDevice> = entries
+        .flatten()
+        .filter(|entry| read_hex(&entry.path().join("idVendor")) == Some(BEACN_VENDOR_ID))
+        .map(|entry| {
+            let path = entry.path();
+            let card = find_sound_card(&path);
+            BeacnDevice {
+                product: read_attr(&path.join("product")).unwrap_or_else(|| "BEACN device".to_string()),
+                product_id: read_hex(&path.join("idProduct")).unwrap_or(0) as u32,
+                serial: read_attr(&path.join("serial")),
+                usb_path: entry.file_name().to_string_lossy().into_owned(),
+                alsa_card: card.as_ref().map(|(index, _)| *index),
+                alsa_card_name: card.and_then(|(_, name)| name),
+            }
+        })
+        .collect();
+
+    devices.sort_by(|a, b| a.usb_path.cmp(&b.usb_path));
+    devices
+}
+
+/// The ALSA card registered by one of the device's interfaces (`<iface>/sound/cardN`).
+fn find_sound_card(device: &Path) -> Option<(u32, Option<String>)> {
+    for interface in fs::read_dir(device).ok()?.flatten() {
+        let cards = match fs::read_dir(interface.path().join("sound")) {
+            Ok(c) => c,
+            Err(_) => continue,
+        };
+
+        for card in cards.flatten() {
+            let file_name = card.file_name();
+            if let Some(index) = file_name.to_str().and_then(|n| n.strip_prefix("card")).and_then(|n| n.parse().ok()) {
+                return Some((index, read_attr(&card.path().join("id"))));
+            }
+        }
+    }
+    None
+}
+
+fn read_attr(path: &Path) -> Option<String> {
+    fs::read_to_string(path).ok().map(|s| s.trim().to_string()).filter(|s| !s.is_empty())
+}
+
+fn read_hex(path: &Path) -> Option<u16> {
+    u16::from_str_radix(&read_attr(path)?, 16).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_beacn_devices_and_their_alsa_card() {
+        let root = std::env::temp_dir().join(format!("beacn-usb-{}", std::process::id()));
+        let write = |rel: &str, contents: &str| {
+            let path = root.join(rel);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, contents).unwrap();
+        };
+
+        write("1-4/idVendor", "33ae\n");
+        write("1-4/idProduct", "0001\n");
+        write("1-4/product", "BEACN Mic\n");
+        write("1-4/1-4:1.0/sound/card2/id", "Mic\n");
+        write("1-2/idVendor", "046d\n");
+        write("1-2/product", "USB Receiver\n");
+        write("1-1:1.0/bInterfaceClass", "09\n");
+
+        let devices = scan_beacn_devices(&root);
+        fs::remove_dir_all(&root).unwrap();
+
+        assert_eq!(devices.len(), 1);
+        assert_eq!(devices[0].product, "BEACN Mic");
+        assert_eq!(devices[0].product_id, 1);
+        assert_eq!(devices[0].serial, None);
+        assert_eq!(devices[0].usb_path, "1-4");
+        assert_eq!(devices[0].alsa_card, Some(2));
+        assert_eq!(devices[0].alsa_card_name.as_deref(), Some("Mic"));
+    }
+}
@@ -1,5 +1,21 @@
 use napi_derive::napi;
 
+pub mod backend;
+pub mod beacn_audio;
+pub mod cleanup;
+mod config;
+mod connection;
+mod error;
+pub mod hardware;
+pub mod logging;
+pub mod metering;
+mod native;
+pub mod pactl;
+pub mod recording;
+mod tone;
+pub mod volume;
+mod worker;
+
 #[napi]
 pub fn beacn_init() -> bool {
     true
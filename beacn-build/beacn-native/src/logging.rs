@@ -0,0 +1,70 @@
+use std::fmt;
+use std::sync::atomic::{AtomicU8, Ordering};
+
+use napi::threadsafe_function::{ErrorStrategy, ThreadsafeFunction};
+use napi_derive::napi;
+use parking_lot::Mutex;
+
+/// Severity of a log record, most severe first.
+#[napi(string_enum)]
+#[derive(Debug, PartialEq)]
+pub enum LogLevel {
+    Error,
+    Warn,
+    Info,
+    /// Also every command the crate runs, with its arguments
+    Debug,
+}
+
+#[napi(object)]
+pub struct LogRecord {
+    pub level: LogLevel,
+    pub message: String,
+}
+
+// Warnings and errors only until the host asks for more
+static LOG_LEVEL: AtomicU8 = AtomicU8::new(LogLevel::Warn as u8);
+// Without a callback records go to stderr, as they always have
+static LOG_CALLBACK: Mutex<Option<ThreadsafeFunction<LogRecord, ErrorStrategy::Fatal>>> = Mutex::new(None);
+
+pub fn set_log_level(level: LogLevel) {
+    LOG_LEVEL.store(level as u8, Ordering::Relaxed);
+}
+
+pub fn set_log_callback(callback: Option<ThreadsafeFunction<LogRecord, ErrorStrategy::Fatal>>) {
+    *LOG_CALLBACK.lock() = callback;
+}
+
+/// Sends `message` to the log callback, or stderr, if `level` is enabled. Safe to
+/// call from any thread.
+pub fn log(level: LogLevel, message: fmt::Arguments) {
+    if level as u8 > LOG_LEVEL.load(Ordering::Relaxed) {
+        return;
+    }
+
+    match LOG_CALLBACK.lock().as_ref() {
+        // Test binaries have no JS host to link the callback against, and never set one
+        #[cfg(not(test))]
+        Some(callback) => {
+            let record = LogRecord { level, message: message.to_string() };
+            callback.call(record, napi::threadsafe_function::ThreadsafeFunctionCallMode::NonBlocking);
+        }
+        _ => eprintln!("{}", message),
+    }
+}
+
+pub fn error(message: fmt::Arguments) {
+    log(LogLevel::Error, message);
+}
+
+pub fn warn(message: fmt::Arguments) {
+    log(LogLevel::Warn, message);
+}
+
+pub fn info(message: fmt::Arguments) {
+    log(LogLevel::Info, message);
+}
+
+pub fn debug(message: fmt::Arguments) {
+    log(LogLevel::Debug, message);
+}
@@ -0,0 +1,123 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use libpulse_binding as pulse;
+use napi::threadsafe_function::{ErrorStrategy, ThreadsafeFunction, ThreadsafeFunctionCallMode};
+use napi_derive::napi;
+use pulse::def::BufferAttr;
+use pulse::mainloop::standard::IterateResult;
+use pulse::sample::{Format, Spec};
+use pulse::stream::{FlagSet, PeekResult, State, Stream};
+
+use crate::connection::connect_context;
+use crate::logging;
+
+// Levels are only needed for display, so a low capture rate keeps the stream cheap
+const METER_SAMPLE_RATE: u32 = 8000;
+// Caps callbacks at ~30 Hz
+const METER_INTERVAL: Duration = Duration::from_millis(33);
+const METER_POLL_MS: u64 = 5;
+
+#[napi(object)]
+pub struct MeterLevel {
+    /// Highest absolute sample in the interval, 0.0-1.0
+    pub peak: f64,
+    /// Root mean square over the interval, 0.0-1.0
+    pub rms: f64,
+}
+
+/// Records `monitor` as mono floats on a private connection and reports the level of
+/// each interval to `tsfn` until `stop` is set. Runs on a metering worker thread.
+pub fn run_meter(
+    monitor: String,
+    server: Option<String>,
+    iterations: u32,
+    tsfn: ThreadsafeFunction<MeterLevel, ErrorStrategy::Fatal>,
+    stop: Arc<AtomicBool>,
+) {
+    let (mut mainloop, mut context) = match connect_context(server.as_deref(), iterations) {
+        Ok(c) => c,
+        Err(e) => {
+            logging::error(format_args!("Meter for {} could not connect: {}", monitor, e));
+            return;
+        }
+    };
+
+    let spec = Spec { format: Format::FLOAT32NE, channels: 1, rate: METER_SAMPLE_RATE };
+    let Some(mut stream) = Stream::new(&mut context, "BEACN Link meter", &spec, None) else {
+        logging::error(format_args!("Meter for {} could not create a stream", monitor));
+        return;
+    };
+
+    // Ask for fragments of roughly one interval so reads line up with callbacks
+    let fragment = spec.usec_to_bytes(pulse::time::MicroSeconds(METER_INTERVAL.as_micros() as u64)) as u32;
+    let attr = BufferAttr {
+        maxlength: u32::MAX,
+        tlength: u32::MAX,
+        prebuf: u32::MAX,
+        minreq: u32::MAX,
+        fragsize: fragment,
+    };
+
+    if let Err(e) = stream.connect_record(Some(&monitor), Some(&attr), FlagSet::ADJUST_LATENCY | FlagSet::DONT_MOVE) {
+        logging::error(format_args!("Meter for {} could not connect its stream: {}", monitor, e));
+        return;
+    }
+
+    let mut peak = 0f32;
+    let mut sum_squares = 0f64;
+    let mut samples = 0usize;
+    let mut last_emit = Instant::now();
+
+    while !stop.load(Ordering::Relaxed) {
+        match mainloop.iterate(false) {
+            IterateResult::Quit(_) | IterateResult::Err(_) => break,
+            IterateResult::Success(_) => {}
+        }
+
+        match stream.get_state() {
+            State::Ready => {}
+            State::Failed | State::Terminated => break,
+            _ => {
+                std::thread::sleep(Duration::from_millis(METER_POLL_MS));
+                continue;
+            }
+        }
+
+        loop {
+            match stream.peek() {
+                Ok(PeekResult::Data(data)) => {
+                    for chunk in data.chunks_exact(4) {
+                        let sample = f32::from_ne_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]);
+                        peak = peak.max(sample.abs());
+                        sum_squares += (sample as f64) * (sample as f64);
+                        samples += 1;
+                    }
+                    let _ = stream.discard();
+                }
+                Ok(PeekResult::Hole(_)) => {
+                    let _ = stream.discard();
+                }
+                Ok(PeekResult::Empty) | Err(_) => break,
+            }
+        }
+
+        if last_emit.elapsed() >= METER_INTERVAL {
+            let rms = if samples == 0 { 0.0 } else { (sum_squares / samples as f64).sqrt() };
+            tsfn.call(
+                MeterLevel { peak: peak.min(1.0) as f64, rms: rms.min(1.0) },
+                ThreadsafeFunctionCallMode::NonBlocking,
+            );
+            peak = 0.0;
+            sum_squares = 0.0;
+            samples = 0;
+            last_emit = Instant::now();
+        }
+
+        std::thread::sleep(Duration::from_millis(METER_POLL_MS));
+    }
+
+    let _ = stream.disconnect();
+    context.disconnect();
+}
@@ -0,0 +1,187 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+
+use libpulse_binding as pulse;
+use pulse::callbacks::ListResult;
+use pulse::context::ext_stream_restore::Info;
+use pulse::context::{Context, State as ContextState};
+use pulse::mainloop::standard::{IterateResult, Mainloop};
+use pulse::operation::{Operation, State};
+use pulse::proplist::UpdateMode;
+
+use crate::beacn_audio::{AudioDevice, DeviceKind};
+use crate::error::BeacnError;
+use crate::pactl::command_timeout;
+
+const NATIVE_POLL_MS: u64 = 5;
+
+/// Sinks and sources straight from the server, in the same shape `pactl` parsing gives.
+pub fn native_devices(mainloop: &mut Mainloop, context: &Context) -> Result<Vec<AudioDevice>, BeacnError> {
+    ensure_ready(context)?;
+    let devices = Rc::new(RefCell::new(Vec::new()));
+    let failed = Rc::new(RefCell::new(false));
+    let introspect = context.introspect();
+
+    let (list, error) = (devices.clone(), failed.clone());
+    wait_for(
+        mainloop,
+        introspect.get_sink_info_list(move |result| match result {
+            ListResult::Item(sink) => list.borrow_mut().extend(sink.name.as_ref().map(|name| AudioDevice {
+                name: name.to_string(),
+                id: sink.index.to_string(),
+                index: sink.index,
+                description: sink.description.as_deref().unwrap_or("").to_string(),
+                kind: DeviceKind::Sink,
+                is_monitor: false,
+                monitor_of: None,
+                sample_rate: sink.sample_spec.rate,
+                channels: sink.sample_spec.channels,
+            })),
+            ListResult::Error => *error.borrow_mut() = true,
+            ListResult::End => {}
+        }),
+    )?;
+
+    ensure_ready(context)?;
+    let (list, error) = (devices.clone(), failed.clone());
+    wait_for(
+        mainloop,
+        introspect.get_source_info_list(move |result| match result {
+            ListResult::Item(source) => list.borrow_mut().extend(source.name.as_ref().map(|name| {
+                let monitor_of = source.monitor_of_sink_name.as_ref().map(|sink| sink.to_string());
+                AudioDevice {
+                    name: name.to_string(),
+                    id: source.index.to_string(),
+                    index: source.index,
+                    description: source.description.as_deref().unwrap_or("").to_string(),
+                    kind: if monitor_of.is_some() { DeviceKind::MonitorSource } else { DeviceKind::Source },
+                    is_monitor: monitor_of.is_some(),
+                    monitor_of,
+                    sample_rate: source.sample_spec.rate,
+                    channels: source.sample_spec.channels,
+                }
+            })),
+            ListResult::Error => *error.borrow_mut() = true,
+            ListResult::End => {}
+        }),
+    )?;
+
+    if *failed.borrow() {
+        return Err(BeacnError::ConnectionFailed("the server could not list its devices".to_string()));
+    }
+    Ok(devices.take())
+}
+
+/// The server details `pactl info` prints, keyed the same way so `parse_info`
+/// callers can use either.
+pub fn native_info(mainloop: &mut Mainloop, context: &Context) -> Result<HashMap<String, String>, BeacnError> {
+    ensure_ready(context)?;
+    let info = Rc::new(RefCell::new(HashMap::new()));
+
+    let map = info.clone();
+    wait_for(
+        mainloop,
+        context.introspect().get_server_info(move |server| {
+            let mut map = map.borrow_mut();
+            let mut insert = |key: &str, value: Option<&str>| {
+                if let Some(value) = value {
+                    map.insert(key.to_string(), value.to_string());
+                }
+            };
+            insert("Server Name", server.server_name.as_deref());
+            insert("Server Version", server.server_version.as_deref());
+            insert("Default Sample Specification", Some(&server.sample_spec.print()));
+            insert("Default Channel Map", Some(&server.channel_map.print()));
+            insert("Default Sink", server.default_sink_name.as_deref());
+            insert("Default Source", server.default_source_name.as_deref());
+        }),
+    )?;
+
+    Ok(info.take())
+}
+
+/// Points the stream-restore rule `rule` (e.g. `sink-input-by-application-name:Discord`)
+/// at `device`, keeping any volume and mute the rule already saved. Only streams
+/// created afterwards are affected.
+pub fn write_stream_device(
+    mainloop: &mut Mainloop,
+    context: &Context,
+    rule: &str,
+    device: &str,
+) -> Result<(), BeacnError> {
+    ensure_ready(context)?;
+    let mut restore = context.stream_restore();
+
+    // Writing replaces the whole entry, so start from the saved one
+    let saved = Rc::new(RefCell::new(None));
+    let (entry, name) = (saved.clone(), rule.to_string());
+    wait_for(
+        mainloop,
+        restore.read(move |result| {
+            if let ListResult::Item(info) = result {
+                if info.name.as_deref() == Some(name.as_str()) {
+                    *entry.borrow_mut() = Some((info.channel_map, info.volume, info.mute));
+                }
+            }
+        }),
+    )
+    .map_err(|_| BeacnError::Unsupported("stream restore rules; is module-stream-restore loaded?".to_string()))?;
+
+    let (channel_map, volume, mute) = saved.take().unwrap_or_default();
+    let info = Info { name: Some(rule.into()), channel_map, volume, device: Some(device.into()), mute };
+
+    ensure_ready(context)?;
+    let written = Rc::new(RefCell::new(false));
+    let success = written.clone();
+    wait_for(
+        mainloop,
+        restore.write(UpdateMode::Replace, &[&info], false, move |ok| *success.borrow_mut() = ok),
+    )?;
+
+    if !*written.borrow() {
+        return Err(BeacnError::Unsupported(format!("writing the stream restore rule {}", rule)));
+    }
+    Ok(())
+}
+
+/// Fails unless `context` can take requests. libpulse hands back no operation at
+/// all on a failed context (e.g. after the server restarted), which the binding
+/// would panic on, so every query checks first and callers fall back to `pactl`.
+fn ensure_ready(context: &Context) -> Result<(), BeacnError> {
+    if context.get_state() != ContextState::Ready {
+        return Err(BeacnError::ConnectionFailed("the connection to the server is not ready".to_string()));
+    }
+    Ok(())
+}
+
+/// Drives `mainloop` until `operation` has run its callback(s), giving up after
+/// the command timeout so a wedged server can't hang the caller.
+fn wait_for<C: ?Sized>(mainloop: &mut Mainloop, mut operation: Operation<C>) -> Result<(), BeacnError> {
+    let timeout = command_timeout();
+    let deadline = Instant::now() + timeout;
+
+    while operation.get_state() == State::Running {
+        match mainloop.iterate(false) {
+            IterateResult::Quit(_) | IterateResult::Err(_) => {
+                return Err(BeacnError::ConnectionFailed("the connection was lost".to_string()))
+            }
+            IterateResult::Success(0) => std::thread::sleep(Duration::from_millis(NATIVE_POLL_MS)),
+            IterateResult::Success(_) => {}
+        }
+
+        if Instant::now() >= deadline {
+            operation.cancel();
+            return Err(BeacnError::ConnectionFailed(format!(
+                "the server did not answer within {} ms",
+                timeout.as_millis()
+            )));
+        }
+    }
+
+    if operation.get_state() == State::Cancelled {
+        return Err(BeacnError::ConnectionFailed("the server dropped the request".to_string()));
+    }
+    Ok(())
+}
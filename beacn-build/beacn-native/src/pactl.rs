@@ -0,0 +1,640 @@
+use std::collections::HashMap;
+use std::io::Read;
+use std::process::{Child, Command, Output, Stdio};
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::time::{Duration, Instant};
+use napi_derive::napi;
+use parking_lot::Mutex;
+
+use crate::error::BeacnError;
+use crate::logging;
+
+const DEFAULT_COMMAND_TIMEOUT_MS: u32 = 5000;
+const COMMAND_POLL_MS: u64 = 10;
+// PulseAudio caps names at 128 bytes, and a sink's monitor appends `.monitor`.
+// Names derived from a user's (`<name>_Sink`, `<master>_EQ`) are checked too.
+const MAX_SINK_NAME_LEN: usize = 120;
+
+// Server every spawned tool talks to; `None` leaves PULSE_SERVER as inherited
+static PULSE_SERVER: Mutex<Option<String>> = Mutex::new(None);
+static COMMAND_TIMEOUT_MS: AtomicU32 = AtomicU32::new(DEFAULT_COMMAND_TIMEOUT_MS);
+
+pub fn set_pulse_server(server: Option<String>) {
+    *PULSE_SERVER.lock() = server;
+}
+
+pub fn pulse_server() -> Option<String> {
+    PULSE_SERVER.lock().clone()
+}
+
+pub fn set_command_timeout(ms: u32) {
+    COMMAND_TIMEOUT_MS.store(ms, Ordering::Relaxed);
+}
+
+/// How long a tool, or a libpulse query, may take before it is given up on.
+pub fn command_timeout() -> Duration {
+    Duration::from_millis(COMMAND_TIMEOUT_MS.load(Ordering::Relaxed) as u64)
+}
+
+pub fn run_pactl(args: &[&str]) -> Result<String, BeacnError> {
+    run_command("pactl", args)
+}
+
+/// Runs an audio CLI tool (`pactl`, `pw-link`, ...) and returns its stdout. A tool
+/// that outlives the command timeout is killed, since a wedged sound server can
+/// otherwise hang `pactl` forever.
+pub fn run_command(program: &str, args: &[&str]) -> Result<String, BeacnError> {
+    let mut command = tool_command(program, args);
+
+    let timeout = command_timeout();
+    let output = output_with_timeout(&mut command, timeout)
+        .map_err(|e| spawn_error(program, args, e))?
+        .ok_or_else(|| BeacnError::CommandTimedOut {
+            command: command_line(program, args),
+            timeout_ms: timeout.as_millis() as u32,
+        })?;
+
+    if !output.status.success() {
+        let error = BeacnError::CommandFailed {
+            command: command_line(program, args),
+            code: output.status.code(),
+            stderr: String::from_utf8_lossy(&output.stderr).into_owned(),
+        };
+        logging::debug(format_args!("{}", error));
+        return Err(error);
+    }
+
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// Starts a long running tool such as `paplay` with the same environment as
+/// `run_command`, leaving the caller to wait for or kill it.
+pub fn spawn_command(program: &str, args: &[&str]) -> Result<Child, BeacnError> {
+    tool_command(program, args)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .map_err(|e| spawn_error(program, args, e))
+}
+
+/// `program` and its arguments as one line, for error messages and logs.
+pub fn command_line(program: &str, args: &[&str]) -> String {
+    std::iter::once(program).chain(args.iter().copied()).collect::<Vec<_>>().join(" ")
+}
+
+fn tool_command(program: &str, args: &[&str]) -> Command {
+    logging::debug(format_args!("running {}", command_line(program, args)));
+    let mut command = Command::new(program);
+    command
+        .args(args)
+        // Field names in the long listings are translated, so pin the locale
+        .env("LC_ALL", "C");
+    if let Some(server) = PULSE_SERVER.lock().as_deref() {
+        command.env("PULSE_SERVER", server);
+    }
+    command
+}
+
+fn spawn_error(program: &str, args: &[&str], e: std::io::Error) -> BeacnError {
+    match e.kind() {
+        std::io::ErrorKind::NotFound if program == "pactl" => BeacnError::PactlNotFound,
+        std::io::ErrorKind::NotFound => BeacnError::NotFound(format!("{} binary", program)),
+        _ => BeacnError::CommandFailed { command: command_line(program, args), code: None, stderr: e.to_string() },
+    }
+}
+
+/// Like `Command::output`, but gives up after `timeout` and returns `None`.
+fn output_with_timeout(command: &mut Command, timeout: Duration) -> std::io::Result<Option<Output>> {
+    let mut child = command.stdin(Stdio::null()).stdout(Stdio::piped()).stderr(Stdio::piped()).spawn()?;
+
+    // Drain both pipes on their own threads so a chatty child can't block on a full pipe
+    let mut stdout = child.stdout.take().expect("stdout is piped");
+    let mut stderr = child.stderr.take().expect("stderr is piped");
+    let stdout_reader = std::thread::spawn(move || {
+        let mut buf = Vec::new();
+        let _ = stdout.read_to_end(&mut buf);
+        buf
+    });
+    let stderr_reader = std::thread::spawn(move || {
+        let mut buf = Vec::new();
+        let _ = stderr.read_to_end(&mut buf);
+        buf
+    });
+
+    let deadline = Instant::now() + timeout;
+    let status = loop {
+        if let Some(status) = child.try_wait()? {
+            break status;
+        }
+        if Instant::now() >= deadline {
+            let _ = child.kill();
+            let _ = child.wait();
+            return Ok(None);
+        }
+        std::thread::sleep(Duration::from_millis(COMMAND_POLL_MS));
+    };
+
+    Ok(Some(Output {
+        status,
+        stdout: stdout_reader.join().unwrap_or_default(),
+        stderr: stderr_reader.join().unwrap_or_default(),
+    }))
+}
+
+/// One `Sink #N` / `Source #N` / ... entry from a long `pactl list` listing.
+#[derive(Debug, Default)]
+pub struct Block {
+    pub index: u32,
+    /// Top level `Key: value` lines
+    pub fields: HashMap<String, String>,
+    /// Indented lines under a valueless `Key:` header such as `Ports:`, trimmed
+    pub sections: HashMap<String, Vec<String>>,
+}
+
+impl Block {
+    pub fn field(&self, key: &str) -> Option<&str> {
+        self.fields.get(key).map(String::as_str)
+    }
+
+    pub fn name(&self) -> Option<&str> {
+        self.field("Name")
+    }
+
+    /// Parsed `Argument:` of a `Module #N` block.
+    pub fn module_args(&self) -> Vec<(String, String)> {
+        parse_module_args(self.field("Argument").unwrap_or(""))
+    }
+
+    /// Looks up a `key = "value"` entry in the `Properties:` section.
+    pub fn property(&self, key: &str) -> Option<&str> {
+        self.sections.get("Properties")?.iter().find_map(|line| {
+            let (k, v) = line.split_once(" = ")?;
+            (k.trim() == key).then(|| v.trim().trim_matches('"'))
+        })
+    }
+}
+
+/// Splits long `pactl list <kind>` output into blocks whose header starts with `header`,
+/// e.g. `"Sink"` for `Sink #3`.
+pub fn parse_blocks(output: &str, header: &str) -> Vec<Block> {
+    let mut blocks: Vec<Block> = Vec::new();
+    let mut section: Option<String> = None;
+    // Off under a header of another kind, e.g. `Sink Input #N` when splitting sinks
+    let mut in_block = false;
+
+    for line in output.lines() {
+        if !line.starts_with(char::is_whitespace) {
+            section = None;
+            in_block = false;
+            if let Some(number) = line.strip_prefix(header).and_then(|rest| rest.trim().strip_prefix('#')) {
+                match number.trim().parse() {
+                    Ok(index) => {
+                        blocks.push(Block { index, ..Default::default() });
+                        in_block = true;
+                    }
+                    Err(_) => logging::warn(format_args!("Skipping pactl block with a non-numeric index: {:?}", line)),
+                }
+            }
+            continue;
+        }
+
+        let Some(block) = blocks.last_mut().filter(|_| in_block) else {
+            continue;
+        };
+
+        if let Some(nested) = line.strip_prefix("\t\t") {
+            // Deeper lines belong to an entry, e.g. a card port's own properties
+            if nested.starts_with('\t') {
+                continue;
+            }
+            if let Some(name) = &section {
+                block.sections.entry(name.clone()).or_default().push(nested.trim().to_string());
+            }
+            continue;
+        }
+
+        // Wrapped continuation lines (e.g. the `balance` line under Volume) are indented with spaces
+        let Some(top) = line.strip_prefix('\t').filter(|l| !l.starts_with(' ')) else {
+            continue;
+        };
+
+        if let Some((key, value)) = top.split_once(':') {
+            let value = value.trim();
+            if value.is_empty() {
+                section = Some(key.to_string());
+            } else {
+                section = None;
+                block.fields.insert(key.to_string(), value.to_string());
+            }
+        }
+    }
+
+    blocks
+}
+
+/// Splits a `Profiles:` or `Ports:` entry such as
+/// `analog-output: Analog Output (type: Line, priority: 9900, available)` into its name,
+/// description and the comma separated details in the trailing parentheses.
+pub fn parse_section_entry(line: &str) -> Option<(&str, &str, Vec<&str>)> {
+    let (name, rest) = line.split_once(": ")?;
+    // Descriptions can contain parentheses of their own, so split at the last group
+    match rest.rfind(" (") {
+        Some(open) if rest.ends_with(')') => {
+            Some((name, &rest[..open], rest[open + 2..rest.len() - 1].split(", ").collect()))
+        }
+        _ => Some((name, rest, Vec::new())),
+    }
+}
+
+pub fn find_sink(name: &str) -> Result<Block, BeacnError> {
+    find_block("sinks", "Sink", name)
+}
+
+pub fn find_source(name: &str) -> Result<Block, BeacnError> {
+    find_block("sources", "Source", name)
+}
+
+fn find_block(kind: &str, header: &str, name: &str) -> Result<Block, BeacnError> {
+    parse_blocks(&run_pactl(&["list", kind])?, header)
+        .into_iter()
+        .find(|b| b.name() == Some(name))
+        .ok_or_else(|| BeacnError::NotFound(format!("{} '{}'", header.to_lowercase(), name)))
+}
+
+/// Parses the flat `Key: value` lines printed by `pactl info`.
+pub fn parse_info(output: &str) -> HashMap<String, String> {
+    output
+        .lines()
+        .filter_map(|line| line.split_once(':'))
+        .map(|(k, v)| (k.trim().to_string(), v.trim().to_string()))
+        .collect()
+}
+
+pub fn list_modules() -> Result<Vec<Block>, BeacnError> {
+    Ok(parse_blocks(&run_pactl(&["list", "modules"])?, "Module"))
+}
+
+/// Pulls the per-channel percentages out of a `Volume:` value such as
+/// `front-left: 65536 / 100% / 0.00 dB,   front-right: 65536 / 100% / 0.00 dB`.
+pub fn parse_channel_percents(volume: &str) -> Vec<u32> {
+    volume
+        .split(',')
+        .filter_map(|channel| {
+            channel
+                .split('/')
+                .map(str::trim)
+                .find_map(|part| part.strip_suffix('%'))
+                .and_then(|pct| pct.trim().parse().ok())
+        })
+        .collect()
+}
+
+pub fn average_percent(percents: &[u32]) -> u32 {
+    if percents.is_empty() {
+        return 0;
+    }
+    (percents.iter().sum::<u32>() as f64 / percents.len() as f64).round() as u32
+}
+
+/// The current latency from a `Latency:` value such as `1250 usec, configured 20000 usec`.
+pub fn parse_latency_usec(latency: &str) -> Option<u64> {
+    latency.split_whitespace().next()?.parse().ok()
+}
+
+/// The latency the device was configured for, from the same `Latency:` value.
+pub fn parse_configured_latency_usec(latency: &str) -> Option<u64> {
+    latency.split_once("configured ")?.1.split_whitespace().next()?.parse().ok()
+}
+
+/// Splits a `Sample Specification:` value such as `s16le 2ch 44100Hz` into
+/// `(format, channels, rate)`.
+pub fn parse_sample_spec(spec: &str) -> Option<(String, u32, u32)> {
+    let mut parts = spec.split_whitespace();
+    let format = parts.next()?.to_string();
+    let channels = parts.next()?.strip_suffix("ch")?.parse().ok()?;
+    let rate = parts.next()?.strip_suffix("Hz")?.parse().ok()?;
+    Some((format, channels, rate))
+}
+
+pub fn parse_yes_no(value: Option<&str>) -> bool {
+    value == Some("yes")
+}
+
+/// Splits a module `Argument:` string into `key=value` pairs the way PulseAudio's
+/// modargs parser does: values may be wrapped in `'` or `"`, and a backslash escapes
+/// the next character. Quotes inside a value (`device.description="A B"`) are kept
+/// but still protect embedded whitespace.
+pub fn parse_module_args(args: &str) -> Vec<(String, String)> {
+    let mut pairs = Vec::new();
+    let mut chars = args.chars().peekable();
+
+    loop {
+        while chars.next_if(|c| c.is_whitespace()).is_some() {}
+        if chars.peek().is_none() {
+            break;
+        }
+
+        let mut key = String::new();
+        while let Some(c) = chars.next_if(|&c| c != '=' && !c.is_whitespace()) {
+            key.push(c);
+        }
+
+        let mut value = String::new();
+        if chars.next_if_eq(&'=').is_some() {
+            let enclosing = chars.next_if(|&c| c == '"' || c == '\'');
+            let mut inner: Option<char> = None;
+
+            while let Some(c) = chars.next() {
+                match c {
+                    '\\' => value.extend(chars.next()),
+                    c if Some(c) == enclosing => break,
+                    c if enclosing.is_none() && inner.is_none() && c.is_whitespace() => break,
+                    '"' | '\'' if enclosing.is_none() => {
+                        inner = match inner {
+                            Some(q) if q == c => None,
+                            None => Some(c),
+                            other => other,
+                        };
+                        value.push(c);
+                    }
+                    c => value.push(c),
+                }
+            }
+        }
+
+        pairs.push((key, value));
+    }
+
+    pairs
+}
+
+pub fn module_arg<'a>(args: &'a [(String, String)], key: &str) -> Option<&'a str> {
+    args.iter().find(|(k, _)| k == key).map(|(_, v)| v.as_str())
+}
+
+/// Maps `name` onto the characters `validate_sink_name` accepts: surrounding whitespace
+/// is dropped, every other disallowed character (spaces, symbols, each non-ASCII
+/// character) becomes `_`, and the result is cut to the maximum length. The creation
+/// methods reject bad names rather than rename them, so a UI taking free-form input
+/// calls this first, e.g. `createVirtualOutput(normalizeSinkName(input))`.
+#[napi]
+pub fn normalize_sink_name(name: String) -> String {
+    name.trim()
+        .chars()
+        .map(|c| if is_sink_name_char(c) { c } else { '_' })
+        .take(MAX_SINK_NAME_LEN)
+        .collect()
+}
+
+/// Checks a sink/source name against the characters PulseAudio accepts in `sink_name=`.
+pub fn validate_sink_name(name: &str) -> Result<(), BeacnError> {
+    if name.trim().is_empty() {
+        return Err(BeacnError::InvalidArgument("device name is empty".to_string()));
+    }
+
+    if name.len() > MAX_SINK_NAME_LEN {
+        return Err(BeacnError::InvalidArgument(format!(
+            "device name {:?} is {} characters long; the maximum is {}",
+            name,
+            name.len(),
+            MAX_SINK_NAME_LEN
+        )));
+    }
+
+    if let Some(c) = name.chars().find(|&c| !is_sink_name_char(c)) {
+        return Err(BeacnError::InvalidArgument(format!(
+            "device name {:?} contains {:?}; only A-Z, a-z, 0-9, '_', '.' and '-' are allowed",
+            name, c
+        )));
+    }
+
+    Ok(())
+}
+
+fn is_sink_name_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || matches!(c, '_' | '.' | '-')
+}
+
+/// Builds a `<key>='device.description="..."'` module argument. The description is
+/// quoted twice over: once for the property list and once for the module argument
+/// parser, so quotes and backslashes survive both passes.
+pub fn description_arg(key: &str, description: &str) -> Result<String, BeacnError> {
+    properties_arg(key, description, &[])
+}
+
+/// Like `description_arg`, with `extra` `name=value` properties appended verbatim.
+pub fn properties_arg(key: &str, description: &str, extra: &[&str]) -> Result<String, BeacnError> {
+    if description.trim().is_empty() {
+        return Err(BeacnError::InvalidArgument("device description is empty".to_string()));
+    }
+
+    if description.chars().any(char::is_control) {
+        return Err(BeacnError::InvalidArgument(format!(
+            "device description {:?} contains control characters",
+            description
+        )));
+    }
+
+    let proplist_value = description.replace('\\', "\\\\").replace('"', "\\\"");
+    let mut property = format!("device.description=\"{}\"", proplist_value);
+    for extra in extra {
+        property.push(' ');
+        property.push_str(extra);
+    }
+    Ok(format!("{}='{}'", key, property.replace('\\', "\\\\").replace('\'', "\\'")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn round_trip(description: &str) -> String {
+        let arg = description_arg("sink_properties", description).unwrap();
+        let args = parse_module_args(&arg);
+        assert_eq!(args.len(), 1, "argument split apart: {}", arg);
+        module_arg(&args, "sink_properties").unwrap().to_string()
+    }
+
+    #[test]
+    fn section_entries_split_at_the_last_parenthesis() {
+        let (name, description, details) = parse_section_entry(
+            "output:hdmi-stereo: Digital Stereo (HDMI) Output (sinks: 1, sources: 0, priority: 5900, available: no)",
+        )
+        .unwrap();
+        assert_eq!(name, "output:hdmi-stereo");
+        assert_eq!(description, "Digital Stereo (HDMI) Output");
+        assert_eq!(details, ["sinks: 1", "sources: 0", "priority: 5900", "available: no"]);
+
+        let (name, description, details) = parse_section_entry("off: Off").unwrap();
+        assert_eq!((name, description), ("off", "Off"));
+        assert!(details.is_empty());
+    }
+
+    #[test]
+    fn description_with_quotes_stays_one_argument() {
+        assert_eq!(round_trip(r#"My "Cool" Sink"#), r#"device.description="My \"Cool\" Sink""#);
+    }
+
+    #[test]
+    fn description_with_ticks_and_backslashes_is_escaped() {
+        assert_eq!(round_trip(r"it's a\b"), r#"device.description="it's a\\b""#);
+    }
+
+    #[test]
+    fn description_with_newline_is_rejected() {
+        assert!(description_arg("sink_properties", "evil\ntext").is_err());
+        assert!(description_arg("sink_properties", "   ").is_err());
+    }
+
+    #[test]
+    fn module_arguments_split_like_pulse_modargs() {
+        let loopback = parse_module_args(
+            "source=BEACN_Link_Out.monitor sink=alsa_output.usb-BEACN_Mix-00.analog-stereo \
+             source_output_properties=\"beacn.link.route=1 media.name='BEACN Link Route'\" latency_msec=20",
+        );
+        assert_eq!(module_arg(&loopback, "source"), Some("BEACN_Link_Out.monitor"));
+        assert_eq!(module_arg(&loopback, "sink"), Some("alsa_output.usb-BEACN_Mix-00.analog-stereo"));
+        assert_eq!(
+            module_arg(&loopback, "source_output_properties"),
+            Some("beacn.link.route=1 media.name='BEACN Link Route'")
+        );
+        assert_eq!(module_arg(&loopback, "latency_msec"), Some("20"));
+
+        // Unwrapped quotes are kept but still hold the value together
+        let null_sink = parse_module_args(r#"sink_name=Chat sink_properties=device.description="Game Chat" channels=2"#);
+        assert_eq!(module_arg(&null_sink, "sink_properties"), Some(r#"device.description="Game Chat""#));
+        assert_eq!(module_arg(&null_sink, "channels"), Some("2"));
+
+        let escaped = parse_module_args(r#"sink_properties='device.description="it\'s a\\b"' rate=48000"#);
+        assert_eq!(module_arg(&escaped, "sink_properties"), Some(r#"device.description="it's a\b""#));
+        assert_eq!(module_arg(&escaped, "rate"), Some("48000"));
+
+        assert!(parse_module_args("").is_empty());
+        assert_eq!(parse_module_args("flag"), [("flag".to_string(), String::new())]);
+    }
+
+    const SINKS: &str = "Sink #53
+	State: SUSPENDED
+	Name: BEACN_Link_Out
+	Description: BEACN Link Out
+	Driver: module-null-sink.c
+	Sample Specification: float32le 2ch 48000Hz
+	Channel Map: front-left,front-right
+	Owner Module: 27
+	Mute: no
+	Volume: front-left: 52429 /  80% / -5.81 dB,   front-right: 65536 / 100% / 0.00 dB
+	        balance 0.20
+	Base Volume: 65536 / 100% / 0.00 dB
+	Monitor Source: BEACN_Link_Out.monitor
+	Latency: 0 usec, configured 40000 usec
+	Flags: DECIBEL_VOLUME LATENCY
+	Properties:
+		device.description = \"BEACN Link Out\"
+		device.class = \"abstract\"
+	Ports:
+		analog-output: Analog Output (type: Line, priority: 9900, available)
+	Active Port: analog-output
+	Formats:
+		pcm
+
+Sink Input #112
+	Driver: protocol-native.c
+	Owner Module: 10
+	Sink: 53
+	Format: pcm, format.sample_format = \"\\\"float32le\\\"\"  format.rate = \"48000\"
+	Mute: no
+	Volume: front-left: 65536 / 100% / 0.00 dB,   front-right: 65536 / 100% / 0.00 dB
+	        balance 0.00
+	Buffer Latency: 21333 usec
+	Sink Latency: 0 usec
+	Properties:
+		media.name = \"Playback\"
+		application.name = \"Firefox\"
+";
+
+    #[test]
+    fn sink_listing_splits_into_fields_and_sections() {
+        let sinks = parse_blocks(SINKS, "Sink");
+        assert_eq!(sinks.len(), 1, "a Sink Input is not a Sink");
+
+        let sink = &sinks[0];
+        assert_eq!(sink.index, 53);
+        assert_eq!(sink.name(), Some("BEACN_Link_Out"));
+        assert_eq!(sink.field("Monitor Source"), Some("BEACN_Link_Out.monitor"));
+        assert_eq!(sink.property("device.description"), Some("BEACN Link Out"));
+        assert_eq!(sink.sections["Ports"], ["analog-output: Analog Output (type: Line, priority: 9900, available)"]);
+        assert_eq!(sink.sections["Formats"], ["pcm"]);
+        // The `balance` continuation line doesn't become a field of its own
+        assert!(!sink.fields.keys().any(|k| k.contains("balance")));
+        assert_eq!(sink.field("Base Volume"), Some("65536 / 100% / 0.00 dB"));
+
+        let volume = parse_channel_percents(sink.field("Volume").unwrap());
+        assert_eq!(volume, [80, 100]);
+        assert_eq!(average_percent(&volume), 90);
+    }
+
+    #[test]
+    fn sink_input_listing_keeps_its_own_fields() {
+        let inputs = parse_blocks(SINKS, "Sink Input");
+        assert_eq!(inputs.len(), 1);
+
+        let input = &inputs[0];
+        assert_eq!(input.index, 112);
+        assert_eq!(input.field("Sink"), Some("53"));
+        assert_eq!(input.field("Owner Module"), Some("10"));
+        assert_eq!(input.field("Buffer Latency").and_then(parse_latency_usec), Some(21333));
+        assert_eq!(input.property("application.name"), Some("Firefox"));
+        assert!(input.field("Name").is_none());
+        assert_eq!(parse_channel_percents(input.field("Volume").unwrap()), [100, 100]);
+    }
+
+    #[test]
+    fn module_without_arguments_has_no_argument_field() {
+        let modules = parse_blocks(
+            "Module #3\n\tName: module-always-sink\n\tArgument: \n\tUsage counter: n/a\n\tProperties:\n\t\tmodule.version = \"16.1\"\n",
+            "Module",
+        );
+
+        assert_eq!(modules[0].name(), Some("module-always-sink"));
+        assert_eq!(modules[0].field("Argument"), None);
+        assert!(modules[0].module_args().is_empty());
+        assert_eq!(modules[0].field("Usage counter"), Some("n/a"));
+        assert_eq!(modules[0].property("module.version"), Some("16.1"));
+    }
+
+    #[test]
+    fn latency_is_the_current_value_not_the_configured_one() {
+        assert_eq!(parse_latency_usec("1250 usec, configured 20000 usec"), Some(1250));
+        assert_eq!(parse_latency_usec("0 usec, configured 0 usec"), Some(0));
+        assert_eq!(parse_latency_usec("n/a"), None);
+        assert_eq!(parse_configured_latency_usec("1250 usec, configured 20000 usec"), Some(20000));
+        assert_eq!(parse_configured_latency_usec("1250 usec"), None);
+    }
+
+    #[test]
+    fn sink_names_are_limited_to_pulse_charset() {
+        assert!(validate_sink_name("BEACN_Link_2_Out").is_ok());
+        assert!(validate_sink_name("beacn.link-out").is_ok());
+        assert!(validate_sink_name(r#"My "Cool" Sink"#).is_err());
+        assert!(validate_sink_name("evil\ntext").is_err());
+        assert!(validate_sink_name("sink_name=x").is_err());
+        assert!(validate_sink_name(" ").is_err());
+        assert!(validate_sink_name(&"a".repeat(MAX_SINK_NAME_LEN)).is_ok());
+        assert!(validate_sink_name(&"a".repeat(MAX_SINK_NAME_LEN + 1)).is_err());
+    }
+
+    #[test]
+    fn normalized_names_pass_validation() {
+        let normalize = |name: &str| normalize_sink_name(name.to_string());
+        assert_eq!(normalize("BEACN_Link_Out"), "BEACN_Link_Out");
+        assert_eq!(normalize("  Game Chat\t"), "Game_Chat");
+        assert_eq!(normalize("Müsik 🎵"), "M_sik__");
+        assert_eq!(normalize(&"x".repeat(200)).len(), MAX_SINK_NAME_LEN);
+
+        for name in ["  Game Chat\t", "Müsik 🎵", "sink_name=x y", "a'b\"c"] {
+            assert!(validate_sink_name(&normalize(name)).is_ok(), "{:?}", name);
+        }
+        assert_eq!(normalize("   "), "");
+    }
+}
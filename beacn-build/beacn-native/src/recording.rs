@@ -0,0 +1,233 @@
+use std::fs::File;
+use std::io::{BufWriter, Seek, SeekFrom, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use libpulse_binding as pulse;
+use napi_derive::napi;
+use pulse::mainloop::standard::IterateResult;
+use pulse::sample::{Format, Spec};
+use pulse::stream::{FlagSet, PeekResult, State, Stream};
+
+use crate::connection::connect_context;
+use crate::logging;
+use crate::error::BeacnError;
+
+const RECORD_POLL_MS: u64 = 5;
+const WAV_HEADER_LEN: u32 = 44;
+const WAVE_FORMAT_PCM: u16 = 1;
+const WAVE_FORMAT_IEEE_FLOAT: u16 = 3;
+// The RIFF and data sizes are u32, so no WAV file holds more than this
+const MAX_WAV_DATA_LEN: u32 = u32::MAX - WAV_HEADER_LEN;
+
+#[napi(object)]
+pub struct RecordingHandle {
+    pub id: u32,
+    pub path: String,
+    pub sample_rate: u32,
+    pub channels: u32,
+    /// Sample format written to the file, e.g. `s16le`
+    pub sample_format: String,
+}
+
+/// How samples are captured and described in the WAV header.
+pub struct WavFormat {
+    pub pulse: Format,
+    pub name: &'static str,
+    pub tag: u16,
+    pub bits: u16,
+    pub channels: u8,
+    pub rate: u32,
+}
+
+impl WavFormat {
+    /// Picks the WAV-compatible (little endian) variant of a sink's sample format.
+    /// Formats WAV can't hold as-is, like a-law, are recorded as 16 bit.
+    pub fn for_sink(format: &str, channels: u32, rate: u32) -> Self {
+        let (pulse, name, tag, bits) = match format {
+            "u8" => (Format::U8, "u8", WAVE_FORMAT_PCM, 8),
+            "s24le" | "s24be" => (Format::S24le, "s24le", WAVE_FORMAT_PCM, 24),
+            "s32le" | "s32be" | "s24-32le" | "s24-32be" => (Format::S32le, "s32le", WAVE_FORMAT_PCM, 32),
+            "float32le" | "float32be" => (Format::F32le, "float32le", WAVE_FORMAT_IEEE_FLOAT, 32),
+            _ => (Format::S16le, "s16le", WAVE_FORMAT_PCM, 16),
+        };
+        WavFormat { pulse, name, tag, bits, channels: channels as u8, rate }
+    }
+
+    fn frame_bytes(&self) -> u32 {
+        self.channels as u32 * self.bits as u32 / 8
+    }
+
+    /// Bytes of samples to record: whole frames for `duration_ms`, or as much as a WAV
+    /// file holds for an open-ended recording. A duration too long for one file is
+    /// rejected.
+    pub fn data_limit(&self, duration_ms: Option<u32>) -> Result<u32, BeacnError> {
+        let frame = self.frame_bytes() as u64;
+        let max = MAX_WAV_DATA_LEN as u64 / frame * frame;
+        let Some(ms) = duration_ms else {
+            return Ok(max as u32);
+        };
+
+        let len = self.rate as u64 * ms as u64 / 1000 * frame;
+        if len > max {
+            return Err(BeacnError::InvalidArgument(format!(
+                "{}ms of {} audio does not fit in a WAV file; the most is {}ms",
+                ms,
+                self.name,
+                max / frame * 1000 / self.rate as u64
+            )));
+        }
+        Ok(len as u32)
+    }
+
+    /// Canonical 44 byte RIFF/WAVE header for `data_len` bytes of samples.
+    fn header(&self, data_len: u32) -> Vec<u8> {
+        let mut header = Vec::with_capacity(WAV_HEADER_LEN as usize);
+        header.extend_from_slice(b"RIFF");
+        header.extend_from_slice(&(WAV_HEADER_LEN - 8 + data_len).to_le_bytes());
+        header.extend_from_slice(b"WAVEfmt ");
+        header.extend_from_slice(&16u32.to_le_bytes());
+        header.extend_from_slice(&self.tag.to_le_bytes());
+        header.extend_from_slice(&(self.channels as u16).to_le_bytes());
+        header.extend_from_slice(&self.rate.to_le_bytes());
+        header.extend_from_slice(&(self.rate * self.frame_bytes()).to_le_bytes());
+        header.extend_from_slice(&(self.frame_bytes() as u16).to_le_bytes());
+        header.extend_from_slice(&self.bits.to_le_bytes());
+        header.extend_from_slice(b"data");
+        header.extend_from_slice(&data_len.to_le_bytes());
+        header
+    }
+}
+
+/// Creates `path` with a placeholder header, so a bad path fails before any thread starts.
+pub fn create_wav(path: &str, format: &WavFormat) -> Result<File, BeacnError> {
+    let mut file = File::create(path).map_err(|e| BeacnError::Io(format!("could not create {}: {}", path, e)))?;
+    file.write_all(&format.header(0)).map_err(|e| BeacnError::Io(format!("could not write {}: {}", path, e)))?;
+    Ok(file)
+}
+
+/// Records `monitor` into `file` on a private connection until `stop` is set or
+/// `limit` bytes (from `WavFormat::data_limit`) have been written, then fills in the
+/// header sizes. Runs on a recording worker thread.
+pub fn run_recording(
+    monitor: String,
+    server: Option<String>,
+    iterations: u32,
+    format: WavFormat,
+    file: File,
+    limit: u32,
+    stop: Arc<AtomicBool>,
+) {
+    let (mut mainloop, mut context) = match connect_context(server.as_deref(), iterations) {
+        Ok(c) => c,
+        Err(e) => {
+            logging::error(format_args!("Recording of {} could not connect: {}", monitor, e));
+            return;
+        }
+    };
+
+    let spec = Spec { format: format.pulse, channels: format.channels, rate: format.rate };
+    let Some(mut stream) = Stream::new(&mut context, "BEACN Link recording", &spec, None) else {
+        logging::error(format_args!("Recording of {} could not create a stream", monitor));
+        return;
+    };
+
+    if let Err(e) = stream.connect_record(Some(&monitor), None, FlagSet::DONT_MOVE) {
+        logging::error(format_args!("Recording of {} could not connect its stream: {}", monitor, e));
+        return;
+    }
+
+    let mut writer = BufWriter::new(file);
+    let mut written = 0u32;
+
+    'record: while !stop.load(Ordering::Relaxed) {
+        match mainloop.iterate(false) {
+            IterateResult::Quit(_) | IterateResult::Err(_) => break,
+            IterateResult::Success(_) => {}
+        }
+
+        match stream.get_state() {
+            State::Ready => {}
+            State::Failed | State::Terminated => break,
+            _ => {
+                std::thread::sleep(Duration::from_millis(RECORD_POLL_MS));
+                continue;
+            }
+        }
+
+        loop {
+            match stream.peek() {
+                Ok(PeekResult::Data(data)) => {
+                    // `limit` is whole frames, so a recording never ends mid-frame
+                    let take = data.len().min((limit - written) as usize);
+                    if let Err(e) = writer.write_all(&data[..take]) {
+                        logging::error(format_args!("Recording of {} stopped: {}", monitor, e));
+                        break 'record;
+                    }
+                    written += take as u32;
+                    let _ = stream.discard();
+
+                    if written == limit {
+                        break 'record;
+                    }
+                }
+                Ok(PeekResult::Hole(_)) => {
+                    let _ = stream.discard();
+                }
+                Ok(PeekResult::Empty) | Err(_) => break,
+            }
+        }
+
+        std::thread::sleep(Duration::from_millis(RECORD_POLL_MS));
+    }
+
+    let _ = stream.disconnect();
+    context.disconnect();
+
+    let finished = writer.into_inner().map_err(|e| e.into_error()).and_then(|mut file| {
+        file.seek(SeekFrom::Start(0))?;
+        file.write_all(&format.header(written))
+    });
+    if let Err(e) = finished {
+        logging::error(format_args!("Recording of {} could not finish its WAV header: {}", monitor, e));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn header_describes_the_recorded_format() {
+        let format = WavFormat::for_sink("float32le", 2, 48000);
+        let header = format.header(960);
+
+        assert_eq!(header.len(), WAV_HEADER_LEN as usize);
+        assert_eq!(&header[0..4], b"RIFF");
+        assert_eq!(u32::from_le_bytes(header[4..8].try_into().unwrap()), 36 + 960);
+        assert_eq!(u16::from_le_bytes([header[20], header[21]]), WAVE_FORMAT_IEEE_FLOAT);
+        assert_eq!(u16::from_le_bytes([header[22], header[23]]), 2);
+        assert_eq!(u32::from_le_bytes(header[24..28].try_into().unwrap()), 48000);
+        assert_eq!(u32::from_le_bytes(header[28..32].try_into().unwrap()), 48000 * 8);
+        assert_eq!(u16::from_le_bytes([header[34], header[35]]), 32);
+        assert_eq!(u32::from_le_bytes(header[40..44].try_into().unwrap()), 960);
+
+        // Big endian sinks are captured little endian, as WAV requires
+        assert_eq!(WavFormat::for_sink("s16be", 1, 44100).name, "s16le");
+    }
+
+    #[test]
+    fn data_limit_is_whole_frames_within_a_wav_file() {
+        let format = WavFormat::for_sink("float32le", 2, 48000);
+
+        assert_eq!(format.data_limit(Some(1000)).unwrap(), 48000 * 8);
+        // Four hours is past what u32 sizes can describe at this rate
+        assert!(format.data_limit(Some(4 * 60 * 60 * 1000)).is_err());
+
+        let open_ended = format.data_limit(None).unwrap();
+        assert_eq!(open_ended % 8, 0);
+        assert!(open_ended <= MAX_WAV_DATA_LEN);
+        assert!(MAX_WAV_DATA_LEN - open_ended < 8);
+    }
+}
@@ -0,0 +1,114 @@
+use std::f64::consts::TAU;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use libpulse_binding as pulse;
+use pulse::mainloop::standard::IterateResult;
+use pulse::operation::State as OperationState;
+use pulse::sample::{Format, Spec};
+use pulse::stream::{FlagSet, SeekMode, State, Stream};
+
+use crate::connection::connect_context;
+use crate::logging;
+
+const TONE_SAMPLE_RATE: u32 = 48000;
+// Short ramps at both ends so the tone starts and stops without a click
+const TONE_RAMP_MS: u32 = 5;
+const TONE_POLL_MS: u64 = 5;
+
+/// `duration_ms` of a sine at `freq_hz` as mono floats peaking at `amplitude`.
+pub fn sine_samples(freq_hz: u32, duration_ms: u32, amplitude: f64) -> Vec<f32> {
+    let total = (TONE_SAMPLE_RATE as u64 * duration_ms as u64 / 1000) as usize;
+    let ramp = ((TONE_SAMPLE_RATE * TONE_RAMP_MS / 1000) as usize).min(total / 2).max(1);
+
+    (0..total)
+        .map(|i| {
+            let envelope = (i.min(total - 1 - i) as f64 / ramp as f64).min(1.0);
+            let t = i as f64 / TONE_SAMPLE_RATE as f64;
+            (amplitude * envelope * (TAU * freq_hz as f64 * t).sin()) as f32
+        })
+        .collect()
+}
+
+/// Plays `samples` to `sink` on a private connection and waits for them to drain,
+/// or gives up early once `stop` is set. Runs on a tone worker thread.
+pub fn run_tone(sink: String, server: Option<String>, iterations: u32, samples: Vec<f32>, stop: Arc<AtomicBool>) {
+    let (mut mainloop, mut context) = match connect_context(server.as_deref(), iterations) {
+        Ok(c) => c,
+        Err(e) => {
+            logging::error(format_args!("Test tone for {} could not connect: {}", sink, e));
+            return;
+        }
+    };
+
+    let spec = Spec { format: Format::FLOAT32NE, channels: 1, rate: TONE_SAMPLE_RATE };
+    let Some(mut stream) = Stream::new(&mut context, "BEACN Link test tone", &spec, None) else {
+        logging::error(format_args!("Test tone for {} could not create a stream", sink));
+        return;
+    };
+
+    if let Err(e) = stream.connect_playback(Some(&sink), None, FlagSet::DONT_MOVE, None, None) {
+        logging::error(format_args!("Test tone for {} could not connect its stream: {}", sink, e));
+        return;
+    }
+
+    let bytes: Vec<u8> = samples.iter().flat_map(|s| s.to_ne_bytes()).collect();
+    let mut offset = 0;
+    let mut drain = None;
+
+    while !stop.load(Ordering::Relaxed) {
+        match mainloop.iterate(false) {
+            IterateResult::Quit(_) | IterateResult::Err(_) => break,
+            IterateResult::Success(_) => {}
+        }
+
+        match stream.get_state() {
+            State::Ready => {}
+            State::Failed | State::Terminated => break,
+            _ => {
+                std::thread::sleep(Duration::from_millis(TONE_POLL_MS));
+                continue;
+            }
+        }
+
+        if offset < bytes.len() {
+            // Writes must be whole frames, which for mono floats is 4 bytes
+            let writable = stream.writable_size().unwrap_or(0) / 4 * 4;
+            let end = (offset + writable).min(bytes.len());
+            if end > offset {
+                if let Err(e) = stream.write_copy(&bytes[offset..end], 0, SeekMode::Relative) {
+                    logging::error(format_args!("Test tone for {} stopped: {}", sink, e));
+                    break;
+                }
+                offset = end;
+            }
+        } else {
+            let operation = drain.get_or_insert_with(|| stream.drain(None));
+            if operation.get_state() != OperationState::Running {
+                break;
+            }
+        }
+
+        std::thread::sleep(Duration::from_millis(TONE_POLL_MS));
+    }
+
+    let _ = stream.disconnect();
+    context.disconnect();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sine_ramps_in_and_out_within_amplitude() {
+        let samples = sine_samples(440, 100, 0.5);
+
+        assert_eq!(samples.len(), 4800);
+        assert_eq!(samples[0], 0.0);
+        assert!(samples[samples.len() - 1].abs() < 1e-6);
+        assert!(samples.iter().all(|s| s.abs() <= 0.5));
+        assert!(samples.iter().any(|s| s.abs() > 0.49));
+    }
+}
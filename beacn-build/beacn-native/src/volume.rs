@@ -0,0 +1,73 @@
+use napi_derive::napi;
+
+// PA_VOLUME_NORM: the raw value pactl (and pavucontrol) shows as 100%
+const PA_VOLUME_NORM: f64 = 65536.0;
+// PA_VOLUME_MAX, the largest raw volume the server accepts
+const PA_VOLUME_MAX: f64 = (u32::MAX / 2) as f64;
+
+// PulseAudio's percentages are linear in the raw volume, while its dB scale treats
+// the raw volume as the cube root of the amplitude (see pa_sw_volume_to_dB). So
+// dB = 20 * log10((percent / 100)^3) = 60 * log10(percent / 100).
+
+/// Converts a pactl-style percentage to decibels. 0% is `-Infinity`.
+#[napi]
+pub fn volume_to_db(percent: u32) -> f64 {
+    if percent == 0 {
+        return f64::NEG_INFINITY;
+    }
+    60.0 * (percent as f64 / 100.0).log10()
+}
+
+/// Converts decibels to the nearest pactl-style percentage.
+#[napi]
+pub fn db_to_volume(db: f64) -> u32 {
+    if db.is_nan() || db == f64::NEG_INFINITY {
+        return 0;
+    }
+    raw_to_percent(percent_to_raw_f64(100.0 * 10f64.powf(db / 60.0)))
+}
+
+/// Converts a percentage to the raw `pa_volume_t` value, where 100% is `PA_VOLUME_NORM`.
+#[napi]
+pub fn percent_to_pulse_raw(percent: u32) -> u32 {
+    percent_to_raw_f64(percent as f64)
+}
+
+/// Converts a raw `pa_volume_t` value to the nearest percentage.
+#[napi]
+pub fn raw_to_percent(raw: u32) -> u32 {
+    (raw as f64 * 100.0 / PA_VOLUME_NORM).round() as u32
+}
+
+fn percent_to_raw_f64(percent: f64) -> u32 {
+    (percent * PA_VOLUME_NORM / 100.0).round().clamp(0.0, PA_VOLUME_MAX) as u32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn norm_is_zero_db() {
+        assert_eq!(percent_to_pulse_raw(100), 65536);
+        assert_eq!(raw_to_percent(65536), 100);
+        assert_eq!(volume_to_db(100), 0.0);
+        assert_eq!(db_to_volume(0.0), 100);
+    }
+
+    #[test]
+    fn db_uses_the_cubic_mapping() {
+        // Matches what pactl prints for 50%: 32768 / 50% / -18.06 dB
+        assert!((volume_to_db(50) + 18.06).abs() < 0.01);
+        assert_eq!(db_to_volume(-18.06), 50);
+        assert_eq!(volume_to_db(0), f64::NEG_INFINITY);
+        assert_eq!(db_to_volume(f64::NEG_INFINITY), 0);
+    }
+
+    #[test]
+    fn raw_round_trips() {
+        for percent in [0, 1, 33, 50, 99, 100, 150] {
+            assert_eq!(raw_to_percent(percent_to_pulse_raw(percent)), percent);
+        }
+    }
+}